@@ -0,0 +1,252 @@
+//! Derives translators from D★Mark trees into typed Rust values.
+//!
+//! `#[derive(FromNode)]` implements `TryTranslator<T, ()>` for
+//! `d_mark::TypedTranslator<T>`, so that nodes can be turned into values of
+//! the annotated type:
+//!
+//! * An enum gets one variant per element, marked with
+//!   `#[dmark(element = "name")]`. A variant marked with `#[dmark(text)]`
+//!   holds text, and must have a single field that can be built from a `&str`.
+//! * A struct is built from a single element, given with
+//!   `#[dmark(element = "name")]` on the struct.
+//!
+//! The fields of a struct or variant are attributes, parsed with `FromStr`.
+//! They are required, unless their type is an `Option`. The attribute name is
+//! the field name, unless given with `#[dmark(attribute = "name")]`. The
+//! single field of a tuple struct or variant, or a named field marked with
+//! `#[dmark(children)]`, is built from the children of the element, with
+//! `d_mark::FromChildren`.
+//!
+//! # Examples
+//!
+//! ```
+//! extern crate d_mark;
+//! #[macro_use]
+//! extern crate d_mark_derive;
+//!
+//! use d_mark::{Node, Parser, TypedTranslator};
+//!
+//! #[derive(FromNode, Debug, PartialEq)]
+//! enum Inline {
+//!     #[dmark(element = "em")]
+//!     Em(Vec<Inline>),
+//!     #[dmark(element = "link")]
+//!     Link {
+//!         target: String,
+//!         #[dmark(children)]
+//!         content: Vec<Inline>,
+//!     },
+//!     #[dmark(text)]
+//!     Text(String),
+//! }
+//!
+//! fn main() {
+//!     let parsed = Parser::call("#p %link[target=/x]{a %em{b}}").expect("parsing failed");
+//!     let paragraph = match parsed[0] {
+//!         Node::Element(ref n) => n,
+//!         _ => panic!("expected an element"),
+//!     };
+//!     let inlines = TypedTranslator::<Inline>::new().translate_nodes(paragraph.children());
+//!     assert_eq!(
+//!         inlines,
+//!         Ok(vec![Inline::Link {
+//!             target: "/x".into(),
+//!             content: vec![
+//!                 Inline::Text("a ".into()),
+//!                 Inline::Em(vec![Inline::Text("b".into())]),
+//!             ],
+//!         }])
+//!     );
+//! }
+//! ```
+
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use]
+extern crate quote;
+extern crate syn;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use syn::spanned::Spanned;
+use syn::{Attribute, Data, DeriveInput, Error, Fields, LitStr, Type};
+
+#[proc_macro_derive(FromNode, attributes(dmark))]
+pub fn derive_from_node(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as DeriveInput);
+    match expand(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+/// The options given in `#[dmark(...)]` on a type, variant or field.
+#[derive(Default)]
+struct Options {
+    element: Option<String>,
+    attribute: Option<String>,
+    text: bool,
+    children: bool,
+}
+
+fn options(attrs: &[Attribute]) -> Result<Options, Error> {
+    let mut res = Options::default();
+    for attr in attrs.iter().filter(|a| a.path().is_ident("dmark")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("element") {
+                res.element = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("attribute") {
+                res.attribute = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("text") {
+                res.text = true;
+            } else if meta.path.is_ident("children") {
+                res.children = true;
+            } else {
+                return Err(meta.error("unknown dmark option"));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(res)
+}
+
+fn is_option(ty: &Type) -> bool {
+    match ty {
+        Type::Path(p) => p.path.segments.last().is_some_and(|s| s.ident == "Option"),
+        _ => false,
+    }
+}
+
+/// The expression that builds the given fields from `node`, prefixed by the
+/// path of the struct or variant.
+fn construct(path: TokenStream2, fields: &Fields) -> Result<TokenStream2, Error> {
+    match fields {
+        Fields::Unit => Ok(path),
+        Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+            Ok(quote! { #path(::d_mark::FromChildren::from_children(node)?) })
+        }
+        Fields::Unnamed(fields) => Err(Error::new(
+            fields.span(),
+            "only a single unnamed field, for the children, is supported",
+        )),
+        Fields::Named(fields) => {
+            let mut values = vec![];
+            for field in &fields.named {
+                let ident = field.ident.as_ref().expect("named field");
+                let options = options(&field.attrs)?;
+                let value = if options.children {
+                    quote! { ::d_mark::FromChildren::from_children(node)? }
+                } else {
+                    let name = options.attribute.unwrap_or_else(|| ident.to_string());
+                    if is_option(&field.ty) {
+                        quote! { ::d_mark::parse_attribute(node, #name)? }
+                    } else {
+                        quote! { ::d_mark::require_attribute(node, #name)? }
+                    }
+                };
+                values.push(quote! { #ident: #value });
+            }
+            Ok(quote! { #path { #(#values),* } })
+        }
+    }
+}
+
+fn expand(input: &DeriveInput) -> Result<TokenStream2, Error> {
+    let ident = &input.ident;
+    if !input.generics.params.is_empty() {
+        return Err(Error::new(
+            input.generics.span(),
+            "FromNode cannot be derived for generic types",
+        ));
+    }
+
+    let mut element_arms = vec![];
+    let mut text_variant = None;
+
+    match input.data {
+        Data::Enum(ref data) => {
+            for variant in &data.variants {
+                let variant_ident = &variant.ident;
+                let options = options(&variant.attrs)?;
+                if options.text {
+                    if variant.fields.len() != 1 {
+                        return Err(Error::new(
+                            variant.span(),
+                            "a text variant must have a single field",
+                        ));
+                    }
+                    text_variant = Some(variant_ident);
+                } else if let Some(name) = options.element {
+                    let value = construct(quote! { #ident::#variant_ident }, &variant.fields)?;
+                    element_arms.push(quote! { #name => Ok(#value), });
+                } else {
+                    return Err(Error::new(
+                        variant.span(),
+                        "expected #[dmark(element = \"...\")] or #[dmark(text)]",
+                    ));
+                }
+            }
+        }
+        Data::Struct(ref data) => {
+            let name = options(&input.attrs)?.element.ok_or_else(|| {
+                Error::new(input.span(), "expected #[dmark(element = \"...\")]")
+            })?;
+            let value = construct(quote! { #ident }, &data.fields)?;
+            element_arms.push(quote! { #name => Ok(#value), });
+        }
+        Data::Union(_) => {
+            return Err(Error::new(
+                input.span(),
+                "FromNode cannot be derived for unions",
+            ))
+        }
+    }
+
+    let string_body = match text_variant {
+        Some(variant) => quote! {
+            Ok(#ident::#variant(::std::convert::From::from(node.content())))
+        },
+        None => quote! {
+            Err(::d_mark::TranslationError::new(node, ::d_mark::TypedError::UnexpectedText))
+        },
+    };
+
+    Ok(quote! {
+        impl ::d_mark::TryTranslator<#ident, ()> for ::d_mark::TypedTranslator<#ident> {
+            type Error = ::d_mark::TypedError;
+
+            fn try_translate_element(
+                &self,
+                node: &::d_mark::ElementNode,
+                _context: (),
+            ) -> ::std::result::Result<#ident, ::d_mark::TranslationError<::d_mark::TypedError>> {
+                match node.name() {
+                    #(#element_arms)*
+                    name => Err(::d_mark::TranslationError::new(
+                        node,
+                        ::d_mark::TypedError::UnknownElement(name.to_string()),
+                    )),
+                }
+            }
+
+            fn try_translate_string(
+                &self,
+                node: &::d_mark::StringNode,
+                _context: (),
+            ) -> ::std::result::Result<#ident, ::d_mark::TranslationError<::d_mark::TypedError>> {
+                #string_body
+            }
+
+            fn try_translate_comment(
+                &self,
+                node: &::d_mark::CommentNode,
+                _context: (),
+            ) -> ::std::result::Result<#ident, ::d_mark::TranslationError<::d_mark::TypedError>> {
+                Err(::d_mark::TranslationError::new(
+                    node,
+                    ::d_mark::TypedError::UnexpectedComment,
+                ))
+            }
+        }
+    })
+}
@@ -0,0 +1,123 @@
+extern crate d_mark;
+#[macro_use]
+extern crate d_mark_derive;
+
+use std::fmt::Debug;
+
+use d_mark::{Node, Parser, TypedError, TypedTranslator};
+
+#[derive(FromNode, Debug, PartialEq)]
+enum Inline {
+    #[dmark(element = "em")]
+    Em(Vec<Inline>),
+    #[dmark(element = "code")]
+    Code(String),
+    #[dmark(element = "link")]
+    Link {
+        target: String,
+        title: Option<String>,
+        #[dmark(children)]
+        content: Vec<Inline>,
+    },
+    #[dmark(element = "br")]
+    Br,
+    #[dmark(text)]
+    Text(String),
+}
+
+#[derive(FromNode, Debug, PartialEq)]
+enum Block {
+    #[dmark(element = "p")]
+    Paragraph(Vec<Inline>),
+    #[dmark(element = "h")]
+    Heading {
+        #[dmark(attribute = "level")]
+        depth: u8,
+        #[dmark(children)]
+        content: Vec<Inline>,
+    },
+}
+
+#[derive(FromNode, Debug, PartialEq)]
+#[dmark(element = "release")]
+struct Release {
+    version: u32,
+    #[dmark(children)]
+    notes: Vec<Block>,
+}
+
+fn translate<T>(s: &str) -> Vec<T>
+where
+    TypedTranslator<T>: d_mark::TryTranslator<T, (), Error = TypedError>,
+{
+    let parsed = Parser::call(s).unwrap();
+    TypedTranslator::new().translate_nodes(&parsed).unwrap()
+}
+
+fn error<T: Debug>(s: &str) -> d_mark::TranslationError<TypedError>
+where
+    TypedTranslator<T>: d_mark::TryTranslator<T, (), Error = TypedError>,
+{
+    let parsed = Parser::call(s).unwrap();
+    TypedTranslator::new().translate_nodes(&parsed).unwrap_err()
+}
+
+#[test]
+fn enums() {
+    assert_eq!(
+        translate::<Block>(
+            "#h[level=1] Intro\n#p a %em{b %code{c}}%br{}\n  %link[target=/x,title=X]{d}"
+        ),
+        vec![
+            Block::Heading {
+                depth: 1,
+                content: vec![Inline::Text("Intro".into())],
+            },
+            Block::Paragraph(vec![
+                Inline::Text("a ".into()),
+                Inline::Em(vec![Inline::Text("b ".into()), Inline::Code("c".into())]),
+                Inline::Br,
+                Inline::Text("\n".into()),
+                Inline::Link {
+                    target: "/x".into(),
+                    title: Some("X".into()),
+                    content: vec![Inline::Text("d".into())],
+                },
+            ]),
+        ]
+    );
+}
+
+#[test]
+fn structs() {
+    assert_eq!(
+        translate::<Release>("#release[version=2]\n  #p Fixed.\n  #! todo"),
+        vec![Release {
+            version: 2,
+            notes: vec![Block::Paragraph(vec![Inline::Text("Fixed.".into())])],
+        }]
+    );
+}
+
+#[test]
+fn errors() {
+    let err = error::<Block>("#p a\n#p b %kbd{c}");
+    assert_eq!(err.error, TypedError::UnknownElement("kbd".into()));
+    assert_eq!(err.path, vec![1, 1]);
+
+    let err = error::<Block>("#h[level=high] a");
+    assert_eq!(
+        err.error,
+        TypedError::InvalidAttribute("level".into(), "invalid digit found in string".into())
+    );
+
+    let err = error::<Release>("#release[version=1]\n  #p a\n  b");
+    assert_eq!(err.error, TypedError::UnexpectedText);
+    match *err.node {
+        Node::String(ref n) => assert_eq!(n.content(), "b"),
+        _ => panic!("expected a string"),
+    }
+
+    let err = error::<Release>("#release");
+    assert_eq!(err.error, TypedError::MissingAttribute("version".into()));
+}
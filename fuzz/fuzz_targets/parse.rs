@@ -0,0 +1,14 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate d_mark;
+
+use d_mark::Parser;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(s) = std::str::from_utf8(data) {
+        if let Err(error) = Parser::call(s) {
+            let _ = format!("{}", error);
+        }
+    }
+});
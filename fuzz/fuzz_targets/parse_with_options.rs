@@ -0,0 +1,23 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate d_mark;
+
+use d_mark::{Parser, ParserOptions};
+
+fuzz_target!(|data: &[u8]| {
+    // The first byte selects the options; the rest is the document.
+    if let Some((&flags, rest)) = data.split_first() {
+        if let Ok(s) = std::str::from_utf8(rest) {
+            let options = ParserOptions {
+                unicode_names: flags & 1 != 0,
+                tab_indentation: flags & 2 != 0,
+                keep_comments: flags & 4 != 0,
+                ..ParserOptions::default()
+            };
+            if let Err(error) = Parser::call_with_options(s, options) {
+                let _ = format!("{}", error);
+            }
+        }
+    }
+});
@@ -1,25 +1,143 @@
 extern crate clap;
 extern crate d_mark;
 
-use clap::{App, Arg};
-use d_mark::Parser;
+use clap::{App, Arg, ArgMatches, SubCommand};
+use d_mark::{
+    import_markdown, reindent, DmarkTranslator, LatexTranslator, ManTranslator,
+    MarkdownTranslator, Node, ParserOptions, PlainTextTranslator, ProfileFilter, SourceMap,
+    XmlImporter, XmlTranslator,
+};
 use std::fs::File;
 use std::io;
 use std::io::prelude::*;
+use std::path::Path;
+use std::process;
 
 fn main() {
+    let input = Arg::with_name("input")
+        .help("Sets the input file to use")
+        .index(1);
     let matches = App::new(env!("CARGO_PKG_NAME"))
         .version(env!("CARGO_PKG_VERSION"))
         .author(env!("CARGO_PKG_AUTHORS"))
         .about(env!("CARGO_PKG_DESCRIPTION"))
+        .arg(input.clone())
         .arg(
-            Arg::with_name("input")
-                .help("Sets the input file to use")
-                .index(1),
+            Arg::with_name("unicode-names")
+                .long("unicode-names")
+                .global(true)
+                .help("Allows Unicode element and attribute names"),
+        ).arg(
+            Arg::with_name("tabs")
+                .long("tabs")
+                .global(true)
+                .help("Uses tabs rather than two spaces for indentation"),
+        ).arg(
+            Arg::with_name("raw-blocks")
+                .long("raw-blocks")
+                .global(true)
+                .help("Takes the content of blocks marked [raw] literally"),
+        ).arg(
+            Arg::with_name("profile")
+                .long("profile")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .global(true)
+                .help("Leaves out elements that are not meant for this profile (repeatable)"),
+        ).arg(
+            Arg::with_name("fix")
+                .long("fix")
+                .help("Re-indents the input file in place (or to stdout, for stdin)"),
+        ).subcommand(
+            SubCommand::with_name("convert")
+                .about("Converts the input file to another format")
+                .arg(input)
+                .arg(
+                    Arg::with_name("from")
+                        .long("from")
+                        .takes_value(true)
+                        .default_value("dmark")
+                        .possible_values(&["dmark", "markdown", "xml", "xhtml"])
+                        .help("Sets the input format"),
+                ).arg(
+                    Arg::with_name("to")
+                        .long("to")
+                        .takes_value(true)
+                        .default_value("dmark")
+                        .possible_values(&[
+                            "dmark", "markdown", "latex", "text", "man", "xml", "docbook",
+                        ]).help("Sets the output format"),
+                ).arg(
+                    Arg::with_name("title")
+                        .long("title")
+                        .takes_value(true)
+                        .help("Sets the title of a man page (default: the input file name)"),
+                ).arg(
+                    Arg::with_name("section")
+                        .long("section")
+                        .takes_value(true)
+                        .default_value("1")
+                        .help("Sets the section of a man page"),
+                ).arg(
+                    Arg::with_name("width")
+                        .long("width")
+                        .takes_value(true)
+                        .default_value("72")
+                        .help("Sets the line width of plain text"),
+                ),
         ).get_matches();
+
+    let options = ParserOptions {
+        unicode_names: matches.is_present("unicode-names"),
+        tab_indentation: matches.is_present("tabs"),
+        raw_blocks: matches.is_present("raw-blocks"),
+        ..ParserOptions::default()
+    };
+
+    let profiles = matches
+        .values_of("profile")
+        .map(|v| ProfileFilter::new(&v.collect::<Vec<_>>()));
+
+    if let Some(matches) = matches.subcommand_matches("convert") {
+        convert(matches, options, profiles);
+        return;
+    }
+
     let filename = matches.value_of("input").unwrap_or("-");
+    let contents = read_input(filename);
+
+    // Fix indentation
+    if matches.is_present("fix") {
+        let fixed = reindent(&contents, &options);
+        if filename == "-" {
+            print!("{}", fixed);
+        } else {
+            let mut file = File::create(filename).expect("file not writable");
+            file.write_all(fixed.as_bytes())
+                .expect("file not writable");
+        }
+        return;
+    }
+
+    // Parse
+    let mut sources = SourceMap::new();
+    let file = sources.add(source_name(filename), contents);
+    match sources.parse(file, options).expect("source was just added") {
+        Ok(parsed) => println!("{:#?}", filter_profiles(parsed, &profiles)),
+        Err(error) => println!("{}", sources.render(&error)),
+    };
+}
+
+fn source_name(filename: &str) -> &str {
+    if filename == "-" {
+        "<stdin>"
+    } else {
+        filename
+    }
+}
 
-    // Read file
+fn read_input(filename: &str) -> String {
     let mut contents = String::new();
     if filename == "-" {
         io::stdin()
@@ -30,11 +148,72 @@ fn main() {
         file.read_to_string(&mut contents)
             .expect("file not readable");
     };
+    contents
+}
 
-    // Parse
-    let res = Parser::call(&contents);
-    match res {
-        Ok(parsed) => println!("{:#?}", parsed),
-        Err(error) => println!("{}", error),
+fn filter_profiles(nodes: Vec<Node>, profiles: &Option<ProfileFilter>) -> Vec<Node> {
+    match profiles {
+        Some(filter) => filter.filter(nodes),
+        None => nodes,
+    }
+}
+
+fn convert(matches: &ArgMatches, options: ParserOptions, profiles: Option<ProfileFilter>) {
+    let filename = matches.value_of("input").unwrap_or("-");
+    let contents = read_input(filename);
+
+    let parsed = match matches.value_of("from") {
+        Some("markdown") => Ok(import_markdown(&contents)),
+        Some("xml") => XmlImporter::new().import(&contents).map_err(|e| e.to_string()),
+        Some("xhtml") => XmlImporter::xhtml().import(&contents).map_err(|e| e.to_string()),
+        _ => {
+            let mut sources = SourceMap::new();
+            let file = sources.add(source_name(filename), contents.as_str());
+            sources
+                .parse(file, options.clone())
+                .expect("source was just added")
+                .map_err(|e| sources.render(&e))
+        }
+    };
+    let parsed = parsed.unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        process::exit(1);
+    });
+    let parsed = filter_profiles(parsed, &profiles);
+
+    let output = match matches.value_of("to") {
+        Some("dmark") => DmarkTranslator {
+            tab_indentation: options.tab_indentation,
+            raw_blocks: options.raw_blocks,
+            ..DmarkTranslator::new()
+        }.translate_document(&parsed),
+        Some("markdown") => MarkdownTranslator::new().translate_document(&parsed),
+        Some("latex") => LatexTranslator::new().translate_document(&parsed),
+        Some("text") => {
+            let width = matches
+                .value_of("width")
+                .and_then(|w| w.parse().ok())
+                .unwrap_or_else(|| {
+                    eprintln!("width must be a number");
+                    process::exit(1);
+                });
+            PlainTextTranslator::new(width).translate_document(&parsed)
+        }
+        Some("man") => {
+            let title = matches.value_of("title").map_or_else(
+                || {
+                    Path::new(filename)
+                        .file_stem()
+                        .map_or("stdin".into(), |s| s.to_string_lossy().into_owned())
+                },
+                |t| t.to_string(),
+            );
+            ManTranslator::new(&title, matches.value_of("section").unwrap_or("1"))
+                .translate_document(&parsed)
+        }
+        Some("xml") => XmlTranslator::new().translate_document(&parsed),
+        Some("docbook") => XmlTranslator::docbook().translate_document(&parsed),
+        _ => unreachable!(),
     };
+    print!("{}", output);
 }
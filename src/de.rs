@@ -0,0 +1,794 @@
+use super::util::text_content;
+use super::{ElementNode, Node, ParseError, Parser};
+
+use serde::de::value::BorrowedStrDeserializer;
+use serde::de::{
+    self, Deserialize, DeserializeOwned, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess,
+    SeqAccess, VariantAccess, Visitor,
+};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+use std::slice;
+
+/// The field that gets the text content of an element.
+pub const TEXT_FIELD: &str = "$text";
+
+/// The field that gets all children of an element, as a sequence.
+pub const VALUE_FIELD: &str = "$value";
+
+/// An error while deserializing D★Mark, either in its syntax or in its
+/// structure.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeserializeError {
+    /// The document is not valid D★Mark. Lines and columns start at 1.
+    Parse {
+        error: ParseError,
+        line: usize,
+        column: usize,
+    },
+
+    /// The document does not have the structure of the type.
+    Message(String),
+}
+
+impl fmt::Display for DeserializeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DeserializeError::Parse {
+                error,
+                line,
+                column,
+            } => write!(f, "parse error at line {}, column {}: {:?}", line, column, error),
+            DeserializeError::Message(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl error::Error for DeserializeError {}
+
+impl de::Error for DeserializeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        DeserializeError::Message(msg.to_string())
+    }
+}
+
+/// Parse the given D★Mark document, and deserialize it into a `T`.
+///
+/// The document, and each element, can be deserialized as:
+///
+/// * a struct or map, with a field for each attribute, and one for each name
+///   of child elements. Such a field is a single element, or a sequence of all
+///   child elements with that name. An attribute and child elements with the
+///   same name are an error. The `$text` field gets the text content, and the
+///   `$value` field gets all children;
+/// * a sequence of its children, leaving out comments and text containing only
+///   whitespace;
+/// * an enum, for an element, where the name of the element is the variant,
+///   and its content is the content of the variant. Text is the `$text`
+///   variant;
+/// * a string, number or boolean, from its text content.
+///
+/// Attributes are strings, numbers or booleans, or unit variants of an enum.
+///
+/// # Examples
+///
+/// ```
+/// extern crate d_mark;
+/// #[macro_use]
+/// extern crate serde_derive;
+///
+/// #[derive(Deserialize, Debug, PartialEq)]
+/// struct ReleaseNotes {
+///     title: String,
+///     release: Vec<Release>,
+/// }
+///
+/// #[derive(Deserialize, Debug, PartialEq)]
+/// struct Release {
+///     version: String,
+///     change: Vec<String>,
+/// }
+///
+/// fn main() {
+///     let notes: ReleaseNotes = d_mark::from_str(
+///         "#title Release notes\n#release[version=1.1]\n  #change Fixed a crash",
+///     ).expect("deserializing failed");
+///
+///     assert_eq!(
+///         notes,
+///         ReleaseNotes {
+///             title: "Release notes".into(),
+///             release: vec![Release {
+///                 version: "1.1".into(),
+///                 change: vec!["Fixed a crash".into()],
+///             }],
+///         }
+///     );
+/// }
+/// ```
+pub fn from_str<T: DeserializeOwned>(s: &str) -> Result<T, DeserializeError> {
+    let nodes = Parser::call(s).map_err(|e| DeserializeError::Parse {
+        line: e.line(),
+        column: e.column(),
+        error: e.into_error(),
+    })?;
+    from_nodes(&nodes)
+}
+
+/// Deserialize the given nodes, e.g. a parsed document, into a `T`. See
+/// [`from_str`](fn.from_str.html) for how nodes are mapped.
+pub fn from_nodes<'de, T: Deserialize<'de>>(nodes: &'de [Node]) -> Result<T, DeserializeError> {
+    T::deserialize(Content {
+        name: None,
+        attributes: None,
+        children: nodes,
+    })
+}
+
+fn is_blank(node: &Node) -> bool {
+    match node {
+        Node::Element(_) => false,
+        Node::String(n) => n.content.trim().is_empty(),
+        Node::Comment(_) => true,
+    }
+}
+
+/// The content of an element, or of the whole document, which has no name or
+/// attributes.
+#[derive(Clone, Copy)]
+struct Content<'de> {
+    name: Option<&'de str>,
+    attributes: Option<&'de HashMap<String, String>>,
+    children: &'de [Node],
+}
+
+impl<'de> Content<'de> {
+    fn element(node: &'de ElementNode) -> Self {
+        Content {
+            name: Some(&node.name),
+            attributes: Some(&node.attributes),
+            children: &node.children,
+        }
+    }
+
+    /// The content of a single child node, as an item of a sequence.
+    fn child(node: &'de Node) -> Self {
+        match node {
+            Node::Element(n) => Content::element(n),
+            _ => Content {
+                name: None,
+                attributes: None,
+                children: slice::from_ref(node),
+            },
+        }
+    }
+
+    fn nodes(self) -> impl Iterator<Item = &'de Node> {
+        self.children.iter().filter(|n| !is_blank(n))
+    }
+
+    fn text(self) -> Text<'de> {
+        match self.children {
+            [Node::String(n)] => Text(Cow::Borrowed(&n.content)),
+            children => Text(Cow::Owned(text_content(children))),
+        }
+    }
+
+    fn has_fields(self) -> bool {
+        self.attributes.is_some_and(|a| !a.is_empty())
+            || self.children.iter().any(|n| matches!(n, Node::Element(_)))
+    }
+
+    fn fields(self, names: &[&str]) -> Result<Fields<'de>, DeserializeError> {
+        let mut entries = vec![];
+
+        if let Some(attributes) = self.attributes {
+            let mut attributes: Vec<_> = attributes.iter().collect();
+            attributes.sort();
+            for (key, value) in attributes {
+                entries.push((key.as_str(), Field::Attribute(value)));
+            }
+        }
+
+        let mut groups: Vec<(&str, Vec<&ElementNode>)> = vec![];
+        for child in self.children {
+            if let Node::Element(n) = child {
+                match groups.iter_mut().find(|g| g.0 == n.name) {
+                    Some(group) => group.1.push(n),
+                    None => groups.push((&n.name, vec![n])),
+                }
+            }
+        }
+        for (name, elements) in groups {
+            if self.attributes.is_some_and(|a| a.contains_key(name)) {
+                return Err(de::Error::custom(format!(
+                    "#{} has both an attribute and child elements named {}",
+                    self.name.unwrap_or(""),
+                    name
+                )));
+            }
+            entries.push((name, Field::Elements(elements)));
+        }
+
+        if names.contains(&TEXT_FIELD) {
+            let text = self.text();
+            if !text.0.trim().is_empty() {
+                entries.push((TEXT_FIELD, Field::Text(text)));
+            }
+        }
+        if names.contains(&VALUE_FIELD) {
+            entries.push((VALUE_FIELD, Field::Children(self.children)));
+        }
+
+        entries.reverse();
+        Ok(Fields {
+            entries,
+            value: None,
+        })
+    }
+}
+
+macro_rules! forward_to_text {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeserializeError> {
+                self.text().$method(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Content<'de> {
+    type Error = DeserializeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeserializeError> {
+        if self.has_fields() {
+            self.deserialize_map(visitor)
+        } else {
+            self.text().deserialize_any(visitor)
+        }
+    }
+
+    forward_to_text! {
+        deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64 deserialize_f32
+        deserialize_f64 deserialize_char deserialize_str deserialize_string deserialize_bytes
+        deserialize_byte_buf deserialize_identifier
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeserializeError> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeserializeError> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, DeserializeError> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, DeserializeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeserializeError> {
+        visitor.visit_seq(Sequence(self.nodes().map(Content::child)))
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, DeserializeError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, DeserializeError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeserializeError> {
+        visitor.visit_map(self.fields(&[])?)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DeserializeError> {
+        visitor.visit_map(self.fields(fields)?)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DeserializeError> {
+        if let Some(variant) = self.name {
+            return visitor.visit_enum(Variant {
+                name: variant,
+                content: self,
+            });
+        }
+
+        let mut nodes = self.nodes();
+        match (nodes.next(), nodes.next()) {
+            (Some(Node::Element(n)), None) => {
+                Content::element(n).deserialize_enum(name, variants, visitor)
+            }
+            (Some(Node::String(_)), None) => visitor.visit_enum(Variant {
+                name: TEXT_FIELD,
+                content: self,
+            }),
+            _ => Err(de::Error::custom(format!(
+                "expected a single element for enum {}",
+                name
+            ))),
+        }
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value, DeserializeError> {
+        visitor.visit_unit()
+    }
+}
+
+/// An attribute value, or the text content of an element.
+struct Text<'de>(Cow<'de, str>);
+
+impl<'de> Text<'de> {
+    fn parse<T>(&self) -> Result<T, DeserializeError>
+    where
+        T: ::std::str::FromStr,
+        T::Err: fmt::Display,
+    {
+        self.0.trim().parse().map_err(|e: T::Err| {
+            de::Error::custom(format!("invalid value {:?}: {}", self.0, e))
+        })
+    }
+}
+
+macro_rules! parse_text {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeserializeError> {
+                visitor.$visit(self.parse()?)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Text<'de> {
+    type Error = DeserializeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeserializeError> {
+        match self.0 {
+            Cow::Borrowed(s) => visitor.visit_borrowed_str(s),
+            Cow::Owned(s) => visitor.visit_string(s),
+        }
+    }
+
+    parse_text! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char,
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeserializeError> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, DeserializeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DeserializeError> {
+        visitor.visit_enum(self.0.trim().to_string().into_deserializer())
+    }
+
+    serde::forward_to_deserialize_any! {
+        str string bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+/// The elements with the same name in the content of an element, which are a
+/// single value or a sequence.
+struct Elements<'de>(Vec<&'de ElementNode>);
+
+impl<'de> Elements<'de> {
+    fn single(self) -> Result<Content<'de>, DeserializeError> {
+        match self.0.as_slice() {
+            [node] => Ok(Content::element(node)),
+            nodes => Err(de::Error::custom(format!(
+                "expected a single #{} element, found {}",
+                nodes[0].name,
+                nodes.len()
+            ))),
+        }
+    }
+}
+
+macro_rules! forward_to_single {
+    ($($method:ident($($arg:ident: $ty:ty),*))*) => {
+        $(
+            fn $method<V: Visitor<'de>>(
+                self,
+                $($arg: $ty,)*
+                visitor: V,
+            ) -> Result<V::Value, DeserializeError> {
+                self.single()?.$method($($arg,)* visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Elements<'de> {
+    type Error = DeserializeError;
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeserializeError> {
+        visitor.visit_seq(Sequence(self.0.into_iter().map(Content::element)))
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, DeserializeError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value, DeserializeError> {
+        visitor.visit_unit()
+    }
+
+    forward_to_single! {
+        deserialize_any() deserialize_bool() deserialize_i8() deserialize_i16()
+        deserialize_i32() deserialize_i64() deserialize_u8() deserialize_u16()
+        deserialize_u32() deserialize_u64() deserialize_f32() deserialize_f64()
+        deserialize_char() deserialize_str() deserialize_string() deserialize_bytes()
+        deserialize_byte_buf() deserialize_option() deserialize_unit()
+        deserialize_unit_struct(name: &'static str)
+        deserialize_newtype_struct(name: &'static str)
+        deserialize_tuple_struct(name: &'static str, len: usize)
+        deserialize_map()
+        deserialize_struct(name: &'static str, fields: &'static [&'static str])
+        deserialize_enum(name: &'static str, variants: &'static [&'static str])
+        deserialize_identifier()
+    }
+}
+
+enum Field<'de> {
+    Attribute(&'de str),
+    Elements(Vec<&'de ElementNode>),
+    Text(Text<'de>),
+    Children(&'de [Node]),
+}
+
+/// The fields of an element, in reverse order.
+struct Fields<'de> {
+    entries: Vec<(&'de str, Field<'de>)>,
+    value: Option<Field<'de>>,
+}
+
+impl<'de> MapAccess<'de> for Fields<'de> {
+    type Error = DeserializeError;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, DeserializeError>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        match self.entries.pop() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(BorrowedStrDeserializer::new(key)).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<S>(&mut self, seed: S) -> Result<S::Value, DeserializeError>
+    where
+        S: DeserializeSeed<'de>,
+    {
+        match self.value.take().expect("value requested before key") {
+            Field::Attribute(s) => seed.deserialize(Text(Cow::Borrowed(s))),
+            Field::Elements(elements) => seed.deserialize(Elements(elements)),
+            Field::Text(text) => seed.deserialize(text),
+            Field::Children(children) => seed.deserialize(Content {
+                name: None,
+                attributes: None,
+                children,
+            }),
+        }
+    }
+}
+
+struct Sequence<I>(I);
+
+impl<'de, I> SeqAccess<'de> for Sequence<I>
+where
+    I: Iterator<Item = Content<'de>>,
+{
+    type Error = DeserializeError;
+
+    fn next_element_seed<S>(&mut self, seed: S) -> Result<Option<S::Value>, DeserializeError>
+    where
+        S: DeserializeSeed<'de>,
+    {
+        self.0.next().map(|c| seed.deserialize(c)).transpose()
+    }
+}
+
+/// An element as an enum variant, or text as the `$text` variant.
+struct Variant<'de> {
+    name: &'de str,
+    content: Content<'de>,
+}
+
+impl<'de> EnumAccess<'de> for Variant<'de> {
+    type Error = DeserializeError;
+    type Variant = Content<'de>;
+
+    fn variant_seed<S>(self, seed: S) -> Result<(S::Value, Content<'de>), DeserializeError>
+    where
+        S: DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(BorrowedStrDeserializer::new(self.name))?;
+        Ok((variant, self.content))
+    }
+}
+
+impl<'de> VariantAccess<'de> for Content<'de> {
+    type Error = DeserializeError;
+
+    fn unit_variant(self) -> Result<(), DeserializeError> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<S>(self, seed: S) -> Result<S::Value, DeserializeError>
+    where
+        S: DeserializeSeed<'de>,
+    {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, DeserializeError> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DeserializeError> {
+        visitor.visit_map(self.fields(fields)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_nodes, from_str, DeserializeError};
+    use {ParseError, Parser};
+
+    use std::collections::HashMap;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Changelog {
+        title: String,
+        release: Vec<Release>,
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Release {
+        version: String,
+        date: Option<String>,
+        yanked: Option<bool>,
+        #[serde(rename = "change", default)]
+        changes: Vec<Change>,
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Change {
+        kind: Kind,
+        issue: Option<u32>,
+        #[serde(rename = "$value")]
+        content: Vec<Inline>,
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    #[serde(rename_all = "lowercase")]
+    enum Kind {
+        Added,
+        Fixed,
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    enum Inline {
+        #[serde(rename = "$text")]
+        Text(String),
+        #[serde(rename = "code")]
+        Code(String),
+        #[serde(rename = "link")]
+        Link {
+            target: String,
+            #[serde(rename = "$text")]
+            text: String,
+        },
+    }
+
+    #[test]
+    fn structs() {
+        let changelog: Changelog = from_str(concat!(
+            "#title Changelog\n",
+            "#! Not released yet\n",
+            "#release[version=1.1,date=2024-01-02]\n",
+            "  #change[kind=added] The %code{--fix} option\n",
+            "  #change[kind=fixed,issue=12] A %link[target=/x]{crash}\n",
+            "#release[version=1.0,yanked=true]",
+        )).unwrap();
+
+        assert_eq!(
+            changelog,
+            Changelog {
+                title: "Changelog".into(),
+                release: vec![
+                    Release {
+                        version: "1.1".into(),
+                        date: Some("2024-01-02".into()),
+                        yanked: None,
+                        changes: vec![
+                            Change {
+                                kind: Kind::Added,
+                                issue: None,
+                                content: vec![
+                                    Inline::Text("The ".into()),
+                                    Inline::Code("--fix".into()),
+                                    Inline::Text(" option".into()),
+                                ],
+                            },
+                            Change {
+                                kind: Kind::Fixed,
+                                issue: Some(12),
+                                content: vec![
+                                    Inline::Text("A ".into()),
+                                    Inline::Link {
+                                        target: "/x".into(),
+                                        text: "crash".into(),
+                                    },
+                                ],
+                            },
+                        ],
+                    },
+                    Release {
+                        version: "1.0".into(),
+                        date: None,
+                        yanked: Some(true),
+                        changes: vec![],
+                    },
+                ],
+            }
+        );
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    enum Block {
+        #[serde(rename = "p")]
+        Paragraph(String),
+        #[serde(rename = "h")]
+        Heading(u8, String),
+        #[serde(rename = "hr")]
+        Rule,
+    }
+
+    #[test]
+    fn sequences_and_enums() {
+        let nodes = Parser::call("#p a %em{b}\n#hr\n#h[level=2]\n  #level 2\n  #text c").unwrap();
+        let blocks: Vec<Block> = from_nodes(&nodes).unwrap();
+        assert_eq!(
+            blocks,
+            vec![
+                Block::Paragraph("a b".into()),
+                Block::Rule,
+                Block::Heading(2, "c".into()),
+            ]
+        );
+
+        assert_eq!(from_str::<Block>("#p a"), Ok(Block::Paragraph("a".into())));
+        assert_eq!(from_str::<Vec<u32>>("#n 1\n#n 2"), Ok(vec![1, 2]));
+    }
+
+    #[test]
+    fn maps() {
+        let map: HashMap<String, String> = from_str("#version 1.0\n#name d-mark").unwrap();
+        assert_eq!(map["version"], "1.0");
+        assert_eq!(map["name"], "d-mark");
+    }
+
+    fn error<T: ::serde::de::DeserializeOwned + ::std::fmt::Debug>(s: &str) -> String {
+        let error: DeserializeError = from_str::<T>(s).unwrap_err();
+        error.to_string()
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            error::<Changelog>("#title a\n#title b"),
+            "expected a single #title element, found 2"
+        );
+        assert_eq!(error::<Changelog>("#release[version=1]"), "missing field `title`");
+        assert_eq!(
+            error::<Vec<Change>>("#change[kind=added,issue=x] a"),
+            "invalid value \"x\": invalid digit found in string"
+        );
+        assert_eq!(
+            error::<Vec<Change>>("#change[kind=removed] a"),
+            "unknown variant `removed`, expected `added` or `fixed`"
+        );
+        assert_eq!(
+            error::<Block>("#p a\n#p b"),
+            "expected a single element for enum Block"
+        );
+        assert_eq!(
+            error::<Vec<Release>>("#release[version=1.0]\n  #version 1.1"),
+            "#release has both an attribute and child elements named version"
+        );
+        assert_eq!(
+            from_str::<Block>("#p %b"),
+            Err(DeserializeError::Parse {
+                error: ParseError::UnexpectedEOF,
+                line: 1,
+                column: 6,
+            })
+        );
+        assert_eq!(
+            error::<Block>("#p %b"),
+            "parse error at line 1, column 6: UnexpectedEOF"
+        );
+    }
+}
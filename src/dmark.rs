@@ -0,0 +1,380 @@
+use super::parser::is_raw;
+use super::{CommentNode, ElementNode, Node, StringNode, Translator};
+
+/// Elements that the D★Mark translator writes in block form by default.
+pub const DEFAULT_BLOCK_ELEMENTS: &[&str] = &[
+    "p", "h1", "h2", "h3", "h4", "h5", "h6", "listing", "ul", "ol", "li", "blockquote",
+    "section", "header", "footer", "article", "aside", "nav", "main", "div", "dl", "dt", "dd",
+    "table", "thead", "tbody", "tfoot", "tr", "th", "td", "figure", "figcaption", "pre", "hr",
+];
+
+/// Translates a node tree back to D★Mark source.
+///
+/// Elements listed in `block_elements` are written in block form when they
+/// are in a block themselves; all others are written in inline form. Nodes at
+/// the top level are always written in block form, except for text, which is
+/// wrapped in a `p` element.
+///
+/// Parsing the output gives back the same tree, except for what D★Mark cannot
+/// express: text directly after a nested block starts on a new line, line
+/// breaks in inline elements, attribute values and comments become spaces, and
+/// trailing line breaks in blocks are dropped.
+///
+/// The context is the indentation level of the enclosing block, or `None`
+/// when writing inline content.
+///
+/// # Examples
+///
+/// ```
+/// use d_mark::{DmarkTranslator, Parser};
+///
+/// let parsed = Parser::call("#p[only=web] I %em{love}\n  100%% Rust").expect("parsing failed");
+/// let dmark = DmarkTranslator::new().translate_document(&parsed);
+/// assert_eq!(dmark, "#p[only=web] I %em{love}\n  100%% Rust\n");
+/// ```
+pub struct DmarkTranslator {
+    pub block_elements: Vec<String>,
+    pub tab_indentation: bool,
+
+    /// Write the content of blocks marked as raw literally, for a parser with
+    /// `ParserOptions::raw_blocks` set.
+    pub raw_blocks: bool,
+}
+
+impl Default for DmarkTranslator {
+    fn default() -> Self {
+        DmarkTranslator {
+            block_elements: DEFAULT_BLOCK_ELEMENTS.iter().map(|e| e.to_string()).collect(),
+            tab_indentation: false,
+            raw_blocks: false,
+        }
+    }
+}
+
+impl DmarkTranslator {
+    pub fn new() -> Self {
+        DmarkTranslator::default()
+    }
+
+    /// Translate a whole document, as returned by the parser.
+    pub fn translate_document(&self, nodes: &[Node]) -> String {
+        let mut res = String::new();
+        let mut text = vec![];
+
+        for node in nodes {
+            match node {
+                Node::String(_) => text.push(node),
+                _ => {
+                    res.push_str(&self.translate_top_level_text(&text));
+                    text.clear();
+                    res.push_str(&self.translate_block(node, 0));
+                }
+            }
+        }
+        res.push_str(&self.translate_top_level_text(&text));
+
+        res
+    }
+
+    fn translate_top_level_text(&self, nodes: &[&Node]) -> String {
+        let content: String = nodes
+            .iter()
+            .filter_map(|n| match n {
+                Node::String(s) => Some(s.content.as_ref()),
+                _ => None,
+            }).collect();
+        if content.trim().is_empty() {
+            return String::new();
+        }
+
+        let paragraph = Node::Element(ElementNode {
+            name: "p".into(),
+            attributes: Default::default(),
+            children: vec![Node::String(StringNode {
+                content: content.trim().to_string().into(),
+            })],
+            block: true,
+        });
+        self.translate_block(&paragraph, 0)
+    }
+
+    fn indentation(&self, level: usize) -> String {
+        if self.tab_indentation {
+            "\t".repeat(level)
+        } else {
+            "  ".repeat(level)
+        }
+    }
+
+    fn is_block_name(&self, name: &str) -> bool {
+        self.block_elements.iter().any(|e| e == name)
+    }
+
+    fn is_block_element(&self, node: &Node) -> bool {
+        match node {
+            Node::Element(n) => self.is_block_name(&n.name),
+            _ => false,
+        }
+    }
+
+    /// Translate a node that stands on lines of its own, including the line
+    /// break at the end.
+    fn translate_block(&self, node: &Node, level: usize) -> String {
+        match node {
+            Node::Element(n) => self.translate_block_element(n, level),
+            Node::String(_) => String::new(),
+            Node::Comment(n) => format!(
+                "{}#!{}\n",
+                self.indentation(level),
+                n.content.replace('\n', " ")
+            ),
+        }
+    }
+
+    fn translate_block_element(&self, node: &ElementNode, level: usize) -> String {
+        let mut res = format!("{}#{}{}", self.indentation(level), node.name, attributes(node));
+
+        if self.raw_blocks && is_raw(&node.attributes) {
+            let content = raw_content(&node.children);
+            let mut lines = content.split('\n');
+            if let Some(first) = lines.next().filter(|l| !l.is_empty()) {
+                res.push(' ');
+                res.push_str(first);
+            }
+            res.push('\n');
+            for line in lines {
+                if !line.is_empty() {
+                    res.push_str(&self.indentation(level + 1));
+                    res.push_str(line);
+                }
+                res.push('\n');
+            }
+            return res;
+        }
+
+        let mut inline: Vec<&Node> = vec![];
+        let mut after_block = false;
+        let mut first = true;
+        for child in &node.children {
+            if self.is_block_element(child) {
+                res.push_str(&self.translate_content_lines(&inline, level, first, after_block));
+                inline.clear();
+                res.push_str(&self.translate_block(child, level + 1));
+                after_block = true;
+                first = false;
+            } else {
+                inline.push(child);
+            }
+        }
+        res.push_str(&self.translate_content_lines(&inline, level, first, after_block));
+
+        res
+    }
+
+    /// Translate a run of inline nodes in a block into content lines. The
+    /// first line of the first run goes on the line of the block itself.
+    fn translate_content_lines(
+        &self,
+        nodes: &[&Node],
+        level: usize,
+        first: bool,
+        after_block: bool,
+    ) -> String {
+        let is_blank = nodes.iter().all(|n| match n {
+            Node::String(s) => s.content.trim().is_empty(),
+            _ => false,
+        });
+        if is_blank {
+            return if first { "\n".to_string() } else { String::new() };
+        }
+
+        // Comments between blocks go on lines of their own.
+        if !first && nodes.iter().all(|n| match n {
+            Node::Comment(_) => true,
+            Node::String(s) => s.content.trim().is_empty(),
+            _ => false,
+        }) {
+            return nodes.iter().map(|n| self.translate_block(n, level + 1)).collect();
+        }
+
+        let content: String = nodes.iter().map(|n| self.translate(n, Some(level))).collect();
+        let content = escape_line_starts(&content);
+        let mut lines = content.split('\n');
+        let mut res = String::new();
+
+        if first {
+            let line = lines.next().unwrap_or("");
+            if !line.is_empty() {
+                res.push(' ');
+                res.push_str(line);
+            }
+            res.push('\n');
+        } else if after_block && content.starts_with('\n') {
+            // The parser adds this line break itself.
+            lines.next();
+        }
+
+        while let Some(line) = lines.next() {
+            // Trailing blank lines cannot be expressed.
+            if line.is_empty() && lines.clone().all(str::is_empty) {
+                break;
+            }
+            if !line.is_empty() {
+                res.push_str(&self.indentation(level + 1));
+                res.push_str(line);
+            }
+            res.push('\n');
+        }
+
+        res
+    }
+}
+
+impl Translator<String, Option<usize>> for DmarkTranslator {
+    fn translate_element(&self, node: &ElementNode, level: Option<usize>) -> String {
+        if let Some(level) = level {
+            if self.is_block_name(&node.name) {
+                return self.translate_block_element(node, level);
+            }
+        }
+
+        let content: String = node
+            .children
+            .iter()
+            .map(|c| self.translate(c, None))
+            .collect();
+        format!(
+            "%{}{}{{{}}}",
+            node.name,
+            attributes(node),
+            content.replace('\n', " ")
+        )
+    }
+
+    fn translate_string(&self, node: &StringNode, _level: Option<usize>) -> String {
+        escape(&node.content)
+    }
+
+    fn translate_comment(&self, node: &CommentNode, _level: Option<usize>) -> String {
+        format!(
+            "%!{{{}}}",
+            node.content
+                .replace('%', "%%")
+                .replace('}', "%}")
+                .replace('\n', " ")
+        )
+    }
+}
+
+fn attributes(node: &ElementNode) -> String {
+    if node.attributes.is_empty() {
+        return String::new();
+    }
+
+    let mut attributes: Vec<_> = node.attributes.iter().collect();
+    attributes.sort();
+    let attributes: Vec<String> = attributes
+        .iter()
+        .map(|(k, v)| {
+            if k == v {
+                k.to_string()
+            } else {
+                format!("{}={}", k, escape_attribute(v))
+            }
+        }).collect();
+    format!("[{}]", attributes.join(","))
+}
+
+fn raw_content(nodes: &[Node]) -> String {
+    nodes
+        .iter()
+        .filter_map(|n| match n {
+            Node::String(s) => Some(s.content.as_ref()),
+            _ => None,
+        }).collect()
+}
+
+/// Escape characters that are special in D★Mark text.
+fn escape(s: &str) -> String {
+    s.replace('%', "%%").replace('}', "%}")
+}
+
+/// Escape anything that would make a content line look like a block or a
+/// comment line.
+fn escape_line_starts(s: &str) -> String {
+    s.split('\n')
+        .map(|l| {
+            let trimmed = l.trim_start();
+            if trimmed.starts_with('#') {
+                format!("{}%{}", &l[..l.len() - trimmed.len()], trimmed)
+            } else {
+                l.to_string()
+            }
+        }).collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn escape_attribute(s: &str) -> String {
+    s.replace('%', "%%")
+        .replace(']', "%]")
+        .replace(',', "%,")
+        .replace('\n', " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DmarkTranslator;
+    use util::text_content;
+    use {Parser, ParserOptions};
+
+    fn round_trip(s: &str) -> String {
+        let options = ParserOptions {
+            keep_comments: true,
+            raw_blocks: true,
+            ..ParserOptions::default()
+        };
+        let translator = DmarkTranslator {
+            raw_blocks: true,
+            ..DmarkTranslator::new()
+        };
+        let parsed = Parser::call_with_options(s, options.clone()).unwrap();
+        let res = translator.translate_document(&parsed);
+        let reparsed = Parser::call_with_options(&res, options).unwrap();
+        assert_eq!(text_content(&reparsed), text_content(&parsed));
+        assert_eq!(translator.translate_document(&reparsed), res);
+        res
+    }
+
+    #[test]
+    fn blocks_and_content_lines() {
+        let s = "#section[id=a]\n  #p one %em{two}\n    three\n\n    four\n  #p\n  five\n";
+        assert_eq!(round_trip(s), "#section[id=a]\n  #p one %em{two}\n    three\n\n    four\n  #p\n  five\n");
+    }
+
+    #[test]
+    fn escaping() {
+        assert_eq!(
+            round_trip("#p[a=x%,y%]z%%] 100%% %em{%}} %#b\n  %#c\n  %#!d\n    %#e"),
+            "#p[a=x%,y%]z%%] 100%% %em{%}} #b\n  %#c\n  %#!d\n    %#e\n"
+        );
+    }
+
+    #[test]
+    fn comments_and_raw() {
+        assert_eq!(
+            round_trip("#! top\n#p a %!{x%}} b\n  #p c\n  #! between\n  #p d\n#listing[raw] x\n  #p  y\n\n  z"),
+            "#! top\n#p a %!{x%}} b\n  #p c\n  #! between\n  #p d\n#listing[raw] x\n  #p  y\n\n  z\n"
+        );
+    }
+
+    #[test]
+    fn inline_form_for_unlisted_elements() {
+        let translator = DmarkTranslator {
+            block_elements: vec!["note".into()],
+            tab_indentation: true,
+            raw_blocks: false,
+        };
+        let parsed = Parser::call("#note\n  #p a\n  #note b").unwrap();
+        assert_eq!(translator.translate_document(&parsed), "#note %p{a}\n\t#note b\n");
+    }
+}
@@ -0,0 +1,441 @@
+use super::{ElementNode, Node, Parser, ParserOptions, SourceError, SourceMap, Span};
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// Reads the files that documents include.
+pub trait IncludeLoader {
+    fn load(&self, path: &Path) -> io::Result<String>;
+}
+
+/// Reads included files from the file system.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FileLoader;
+
+impl IncludeLoader for FileLoader {
+    fn load(&self, path: &Path) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+}
+
+/// Reads included files from memory, e.g. in tests.
+impl IncludeLoader for HashMap<PathBuf, String> {
+    fn load(&self, path: &Path) -> io::Result<String> {
+        self.get(path)
+            .cloned()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "file not found"))
+    }
+}
+
+/// An error while resolving includes, with the file it happened in.
+#[derive(Debug)]
+pub enum IncludeError {
+    /// The file could not be read.
+    Io { path: PathBuf, error: io::Error },
+
+    /// The file is not valid D★Mark.
+    Parse(SourceError),
+
+    /// An include element in the file has no source attribute.
+    MissingSource { path: PathBuf },
+
+    /// An include element in the file refers to a file outside the root
+    /// directory.
+    OutsideRoot { path: PathBuf, src: String },
+
+    /// A file includes itself, directly or through other files. The chain of
+    /// files starts and ends with that file.
+    Cycle(Vec<PathBuf>),
+}
+
+impl fmt::Display for IncludeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IncludeError::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            IncludeError::Parse(error) => write!(f, "{}", error),
+            IncludeError::MissingSource { path } => {
+                write!(f, "{}: include without a source", path.display())
+            }
+            IncludeError::OutsideRoot { path, src } => {
+                write!(f, "{}: include outside the root directory: {}", path.display(), src)
+            }
+            IncludeError::Cycle(paths) => {
+                let paths: Vec<String> = paths.iter().map(|p| p.display().to_string()).collect();
+                write!(f, "include cycle: {}", paths.join(" -> "))
+            }
+        }
+    }
+}
+
+/// Expands include elements, e.g. `#include[src=chapter1.dmark]`, into the
+/// nodes of the files they refer to, so that a document can be split across
+/// files.
+///
+/// Sources are relative to the file containing the include element. Included
+/// files are parsed with `options`, and their includes are expanded in turn.
+///
+/// # Examples
+///
+/// ```
+/// use d_mark::{DmarkTranslator, IncludeResolver};
+/// use std::collections::HashMap;
+/// use std::path::{Path, PathBuf};
+///
+/// let mut files = HashMap::new();
+/// files.insert(
+///     PathBuf::from("book/index.dmark"),
+///     "#h1 Book\n#include[src=ch1.dmark]".to_string(),
+/// );
+/// files.insert(PathBuf::from("book/ch1.dmark"), "#h2 Chapter 1".to_string());
+///
+/// let nodes = IncludeResolver::new(files)
+///     .load(Path::new("book/index.dmark"))
+///     .expect("resolving includes failed");
+/// assert_eq!(DmarkTranslator::new().translate_document(&nodes), "#h1 Book\n#h2 Chapter 1\n");
+/// ```
+#[derive(Debug, Clone)]
+pub struct IncludeResolver<L> {
+    pub loader: L,
+
+    /// The name of include elements.
+    pub element: String,
+
+    /// The attribute of include elements that holds the path of the file.
+    pub attribute: String,
+
+    /// The directory that included files must be in, if any. Includes with
+    /// an absolute source, or one that leads out of the directory, are then
+    /// rejected. It is compared with the paths of the files as given, so it
+    /// should be relative if they are.
+    pub root: Option<PathBuf>,
+
+    pub options: ParserOptions,
+}
+
+impl<L: IncludeLoader> IncludeResolver<L> {
+    pub fn new(loader: L) -> Self {
+        IncludeResolver {
+            loader,
+            element: "include".into(),
+            attribute: "src".into(),
+            root: None,
+            options: ParserOptions::default(),
+        }
+    }
+
+    /// Read and parse the given file, and expand its includes.
+    pub fn load(&self, path: &Path) -> Result<Vec<Node>, IncludeError> {
+        self.load_into(path, &mut SourceMap::new())
+    }
+
+    /// Like [`load`](#method.load), but adds the files that were read to the
+    /// given source map, e.g. to show parse errors in context.
+    pub fn load_into(
+        &self,
+        path: &Path,
+        sources: &mut SourceMap,
+    ) -> Result<Vec<Node>, IncludeError> {
+        self.load_file(&normalize(path), &mut vec![], sources)
+    }
+
+    /// Expand the includes in the given nodes, which were read from the file
+    /// at the given path.
+    pub fn resolve(&self, nodes: Vec<Node>, path: &Path) -> Result<Vec<Node>, IncludeError> {
+        self.resolve_into(nodes, path, &mut SourceMap::new())
+    }
+
+    /// Like [`resolve`](#method.resolve), but adds the files that were read
+    /// to the given source map.
+    pub fn resolve_into(
+        &self,
+        nodes: Vec<Node>,
+        path: &Path,
+        sources: &mut SourceMap,
+    ) -> Result<Vec<Node>, IncludeError> {
+        let path = normalize(path);
+        self.resolve_nodes(nodes, &path, &mut vec![path.clone()], sources)
+    }
+
+    fn load_file(
+        &self,
+        path: &Path,
+        stack: &mut Vec<PathBuf>,
+        sources: &mut SourceMap,
+    ) -> Result<Vec<Node>, IncludeError> {
+        if stack.iter().any(|p| p == path) {
+            let mut cycle = stack.clone();
+            cycle.push(path.to_path_buf());
+            return Err(IncludeError::Cycle(cycle));
+        }
+
+        let contents = self.loader.load(path).map_err(|error| IncludeError::Io {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        // The error borrows the contents, so take what is needed from it
+        // before the contents move into the source map.
+        let parsed = Parser::call_with_options(&contents, self.options.clone())
+            .map_err(|e| (e.line(), e.column(), e.into_error()));
+        let file_name = path.display().to_string();
+        let file = sources.add(file_name.clone(), contents);
+        let nodes = parsed.map_err(|(line, column, error)| {
+            IncludeError::Parse(SourceError {
+                error,
+                span: Span { file, line, column },
+                file_name,
+            })
+        })?;
+
+        stack.push(path.to_path_buf());
+        let res = self.resolve_nodes(nodes, path, stack, sources);
+        stack.pop();
+        res
+    }
+
+    /// Expand the includes in nodes read from the file at `path`, which is
+    /// the last one on the stack.
+    fn resolve_nodes(
+        &self,
+        nodes: Vec<Node>,
+        path: &Path,
+        stack: &mut Vec<PathBuf>,
+        sources: &mut SourceMap,
+    ) -> Result<Vec<Node>, IncludeError> {
+        let mut res = vec![];
+        for node in nodes {
+            match node {
+                Node::Element(ref n) if n.name == self.element => {
+                    let src = n.attributes.get(&self.attribute).ok_or_else(|| {
+                        IncludeError::MissingSource {
+                            path: path.to_path_buf(),
+                        }
+                    })?;
+                    let target = normalize(&path.parent().unwrap_or(Path::new("")).join(src));
+                    if !self.is_inside_root(src, &target) {
+                        return Err(IncludeError::OutsideRoot {
+                            path: path.to_path_buf(),
+                            src: src.clone(),
+                        });
+                    }
+                    res.extend(self.load_file(&target, stack, sources)?);
+                }
+                Node::Element(n) => res.push(Node::Element(ElementNode {
+                    children: self.resolve_nodes(n.children, path, stack, sources)?,
+                    ..n
+                })),
+                other => res.push(other),
+            }
+        }
+        Ok(res)
+    }
+
+    fn is_inside_root(&self, src: &str, target: &Path) -> bool {
+        let root = match self.root {
+            Some(ref root) => normalize(root),
+            None => return true,
+        };
+        if Path::new(src).has_root() {
+            return false;
+        }
+        // A normalized path only keeps `..` at its start, where it leads out
+        // of the root.
+        match target.strip_prefix(&root) {
+            Ok(rest) => rest.components().all(|c| c != Component::ParentDir),
+            Err(_) => false,
+        }
+    }
+}
+
+/// Remove `.` and `..` components from the path where possible, so that the
+/// same file is always found at the same path.
+fn normalize(path: &Path) -> PathBuf {
+    let mut res = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => match res.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    res.pop();
+                }
+                Some(Component::RootDir) | Some(Component::Prefix(_)) => (),
+                _ => res.push(".."),
+            },
+            other => res.push(other.as_os_str()),
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{normalize, IncludeError, IncludeResolver};
+    use {DmarkTranslator, Parser, SourceMap};
+
+    use std::collections::HashMap;
+    use std::path::{Path, PathBuf};
+
+    fn files(files: &[(&str, &str)]) -> HashMap<PathBuf, String> {
+        files
+            .iter()
+            .map(|(path, contents)| (PathBuf::from(path), contents.to_string()))
+            .collect()
+    }
+
+    fn load(files: HashMap<PathBuf, String>, path: &str) -> Result<String, IncludeError> {
+        let nodes = IncludeResolver::new(files).load(Path::new(path))?;
+        Ok(DmarkTranslator::new().translate_document(&nodes))
+    }
+
+    #[test]
+    fn nested_and_relative() {
+        let files = files(&[
+            ("book.dmark", "#h1 Book\n#section\n  #include[src=chapters/one.dmark]\n#p End"),
+            ("chapters/one.dmark", "#h2 One\n#include[src=../shared/./note.dmark]"),
+            ("shared/note.dmark", "#p[class=note] A %em{note}"),
+        ]);
+
+        assert_eq!(
+            load(files, "./book.dmark").unwrap(),
+            "#h1 Book\n#section\n  #h2 One\n  #p[class=note] A %em{note}\n#p End\n"
+        );
+    }
+
+    #[test]
+    fn custom_element() {
+        let files = files(&[("a/b.dmark", "#p b")]);
+        let resolver = IncludeResolver {
+            element: "transclude".into(),
+            attribute: "href".into(),
+            ..IncludeResolver::new(files)
+        };
+        let nodes = resolver
+            .resolve(
+                Parser::call("#p a\n#transclude[href=b.dmark]").unwrap(),
+                Path::new("a/index.dmark"),
+            ).unwrap();
+
+        assert_eq!(DmarkTranslator::new().translate_document(&nodes), "#p a\n#p b\n");
+    }
+
+    #[test]
+    fn errors() {
+        let cycle = files(&[
+            ("a.dmark", "#include[src=b/b.dmark]"),
+            ("b/b.dmark", "#include[src=../a.dmark]"),
+        ]);
+        assert_eq!(
+            load(cycle, "a.dmark").unwrap_err().to_string(),
+            "include cycle: a.dmark -> b/b.dmark -> a.dmark"
+        );
+
+        let missing = files(&[("a.dmark", "#include[src=b.dmark]")]);
+        assert_eq!(
+            load(missing, "a.dmark").unwrap_err().to_string(),
+            "b.dmark: file not found"
+        );
+
+        let no_source = files(&[("a.dmark", "#include[src=b.dmark]"), ("b.dmark", "#include")]);
+        assert_eq!(
+            load(no_source, "a.dmark").unwrap_err().to_string(),
+            "b.dmark: include without a source"
+        );
+
+        let invalid = files(&[("a.dmark", "#include[src=b.dmark]"), ("b.dmark", "#p\n#p %x")]);
+        assert_eq!(
+            load(invalid, "a.dmark").unwrap_err().to_string(),
+            "b.dmark:2:6: UnexpectedEOF"
+        );
+    }
+
+    #[test]
+    fn root() {
+        let resolver = IncludeResolver {
+            root: Some(PathBuf::from("./book")),
+            ..IncludeResolver::new(files(&[
+                ("book/index.dmark", "#include[src=ch/one.dmark]"),
+                ("book/ch/one.dmark", "#include[src=../two.dmark]"),
+                ("book/two.dmark", "#p Two"),
+                ("book/up.dmark", "#include[src=../../etc/passwd]"),
+                ("book/abs.dmark", "#include[src=/etc/passwd]"),
+                ("book/sibling.dmark", "#include[src=../other/a.dmark]"),
+                ("other/a.dmark", "#p Other"),
+            ]))
+        };
+        let load = |path| {
+            resolver
+                .load(Path::new(path))
+                .map(|nodes| DmarkTranslator::new().translate_document(&nodes))
+                .map_err(|e| e.to_string())
+        };
+
+        assert_eq!(load("book/index.dmark"), Ok("#p Two\n".to_string()));
+        assert_eq!(
+            load("book/up.dmark"),
+            Err("book/up.dmark: include outside the root directory: ../../etc/passwd".into())
+        );
+        assert_eq!(
+            load("book/abs.dmark"),
+            Err("book/abs.dmark: include outside the root directory: /etc/passwd".into())
+        );
+        assert_eq!(
+            load("book/sibling.dmark"),
+            Err("book/sibling.dmark: include outside the root directory: ../other/a.dmark"
+                .into())
+        );
+
+        let resolver = IncludeResolver {
+            root: Some(PathBuf::from(".")),
+            ..IncludeResolver::new(files(&[("a.dmark", "#include[src=../b.dmark]")]))
+        };
+        assert_eq!(
+            resolver.load(Path::new("a.dmark")).unwrap_err().to_string(),
+            "a.dmark: include outside the root directory: ../b.dmark"
+        );
+    }
+
+    #[test]
+    fn resolve_into() {
+        let resolver = IncludeResolver::new(files(&[("b.dmark", "#p %em{b")]));
+        let mut sources = SourceMap::new();
+
+        let error = match resolver.resolve_into(
+            Parser::call("#include[src=b.dmark]").unwrap(),
+            Path::new("a.dmark"),
+            &mut sources,
+        ) {
+            Err(IncludeError::Parse(error)) => error,
+            res => panic!("unexpected result: {:?}", res),
+        };
+        assert_eq!(sources.name(error.span.file), Some("b.dmark"));
+        assert!(sources.render(&error).contains("\n#p %em{b\n"));
+    }
+
+    #[test]
+    fn sources() {
+        let resolver = IncludeResolver::new(files(&[
+            ("book.dmark", "#include[src=chapters/one.dmark]"),
+            ("chapters/one.dmark", "#p One\n#include[src=two.dmark]"),
+            ("chapters/two.dmark", "#p Two\n\n#p %em{three"),
+        ]));
+        let mut sources = SourceMap::new();
+
+        let error = match resolver.load_into(Path::new("book.dmark"), &mut sources) {
+            Err(IncludeError::Parse(error)) => error,
+            res => panic!("unexpected result: {:?}", res),
+        };
+        assert_eq!(sources.files().count(), 3);
+        assert_eq!(sources.name(error.span.file), Some("chapters/two.dmark"));
+        assert_eq!(error.to_string(), "chapters/two.dmark:3:13: UnexpectedEOF");
+        assert!(sources.render(&error).contains("\n#p %em{three\n"));
+    }
+
+    #[test]
+    fn normalizing() {
+        assert_eq!(normalize(Path::new("./a/../b/./c")), PathBuf::from("b/c"));
+        assert_eq!(normalize(Path::new("../a/..")), PathBuf::from(".."));
+        assert_eq!(normalize(Path::new("/../a")), PathBuf::from("/a"));
+    }
+}
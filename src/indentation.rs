@@ -0,0 +1,251 @@
+use super::ParserOptions;
+
+use std::collections::HashMap;
+
+/// Re-indents a D★Mark document so that it uses the indentation style
+/// described by `options`.
+///
+/// Each line’s leading whitespace is measured in columns, with a tab advancing
+/// to the next level. Odd amounts of indentation are rounded up to the next
+/// level, but never deeper than one level below the last block, so that
+/// over-indented content keeps its extra whitespace. With `raw_blocks` set, the
+/// content of raw blocks is left as it is, apart from the indentation of the
+/// block itself.
+pub fn reindent(s: &str, options: &ParserOptions) -> String {
+    let mut res = String::with_capacity(s.len());
+    let mut max_level = 0;
+    let mut raw_level = None;
+
+    for (i, line) in s.split('\n').enumerate() {
+        if i > 0 {
+            res.push('\n');
+        }
+
+        let rest = line.trim_start_matches([' ', '\t']);
+        let is_blank = rest.is_empty() || rest == "\r";
+
+        if let Some(level) = raw_level {
+            match strip_columns(line, level * 2) {
+                Some(content) if !content.is_empty() && content != "\r" => {
+                    push_indentation(&mut res, level, options);
+                    res.push_str(content);
+                    continue;
+                }
+                _ if is_blank => {
+                    res.push_str(rest);
+                    continue;
+                }
+                _ => raw_level = None,
+            }
+        }
+
+        if is_blank {
+            res.push_str(rest);
+            continue;
+        }
+
+        let columns: usize = line[..line.len() - rest.len()]
+            .chars()
+            .fold(0, |col, c| if c == '\t' { col / 2 * 2 + 2 } else { col + 1 });
+
+        let level = columns.div_ceil(2).min(max_level);
+        let extra = columns.saturating_sub(level * 2);
+
+        push_indentation(&mut res, level, options);
+        for _ in 0..extra {
+            res.push(' ');
+        }
+        res.push_str(rest);
+
+        if is_block_start(rest, options) {
+            max_level = level + 1;
+            if options.is_raw_block(&attributes(rest, options)) {
+                raw_level = Some(level + 1);
+            }
+        }
+    }
+
+    res
+}
+
+fn push_indentation(res: &mut String, level: usize, options: &ParserOptions) {
+    for _ in 0..level * options.indentation_width() {
+        res.push(options.indentation_char());
+    }
+}
+
+/// Remove the given number of columns of indentation from the line, or return
+/// None if it is indented less.
+fn strip_columns(line: &str, columns: usize) -> Option<&str> {
+    let mut col = 0;
+    for (idx, c) in line.char_indices() {
+        if col >= columns {
+            return Some(&line[idx..]);
+        }
+        col = match c {
+            ' ' => col + 1,
+            '\t' => col / 2 * 2 + 2,
+            _ => return None,
+        };
+    }
+
+    if col >= columns {
+        Some("")
+    } else {
+        None
+    }
+}
+
+fn is_block_start(s: &str, options: &ParserOptions) -> bool {
+    let mut chars = s.chars();
+    chars.next() == Some('#') && chars.next().is_some_and(|c| options.is_name_head_char(c))
+}
+
+/// The attributes of the block that the line starts, read loosely: invalid
+/// attributes are not an error here, as the parser reports them.
+fn attributes(s: &str, options: &ParserOptions) -> HashMap<String, String> {
+    let mut res = HashMap::new();
+    let mut chars = s.chars().skip(1).skip_while(|c| options.is_name_tail_char(*c));
+    if chars.next() != Some('[') {
+        return res;
+    }
+
+    let mut attribute = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '%' => attribute.extend(chars.next()),
+            ',' | ']' => {
+                let (key, value) = match attribute.find('=') {
+                    Some(idx) => (attribute[..idx].to_string(), attribute[idx + 1..].to_string()),
+                    None => (attribute.clone(), attribute.clone()),
+                };
+                res.insert(key, value);
+                attribute.clear();
+                if c == ']' {
+                    break;
+                }
+            }
+            _ => attribute.push(c),
+        }
+    }
+
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::reindent;
+    use ParserOptions;
+
+    #[test]
+    fn reindent_unchanged() {
+        let input = "#p hi\n  #p ho\n    ha\n\n#p he";
+        assert_eq!(reindent(input, &ParserOptions::default()), input);
+    }
+
+    #[test]
+    fn reindent_tabs_to_spaces() {
+        assert_eq!(
+            reindent("#p hi\n\t#p ho\n\t\tha", &ParserOptions::default()),
+            "#p hi\n  #p ho\n    ha"
+        );
+    }
+
+    #[test]
+    fn reindent_spaces_to_tabs() {
+        let options = ParserOptions {
+            tab_indentation: true,
+            ..ParserOptions::default()
+        };
+
+        assert_eq!(
+            reindent("#p hi\n  #p ho\n    ha", &options),
+            "#p hi\n\t#p ho\n\t\tha"
+        );
+    }
+
+    #[test]
+    fn reindent_odd() {
+        assert_eq!(
+            reindent("#p hi\n #p ho\n   ha", &ParserOptions::default()),
+            "#p hi\n  #p ho\n    ha"
+        );
+    }
+
+    #[test]
+    fn reindent_mixed() {
+        assert_eq!(
+            reindent("#p hi\n \t#p ho", &ParserOptions::default()),
+            "#p hi\n  #p ho"
+        );
+    }
+
+    #[test]
+    fn reindent_keeps_extra_content_indentation() {
+        assert_eq!(
+            reindent("#p hi\n     ho", &ParserOptions::default()),
+            "#p hi\n     ho"
+        );
+    }
+
+    #[test]
+    fn reindent_crlf() {
+        assert_eq!(
+            reindent("#p hi\r\n\t#p ho\r\n  \r\n", &ParserOptions::default()),
+            "#p hi\r\n  #p ho\r\n\r\n"
+        );
+    }
+
+    fn raw_blocks() -> ParserOptions {
+        ParserOptions {
+            raw_blocks: true,
+            ..ParserOptions::default()
+        }
+    }
+
+    #[test]
+    fn reindent_raw() {
+        let input = concat!(
+            "#p hi\n",
+            "\t#listing[raw,lang=make] all:\n",
+            "\t  \tcc -o x x.c\n",
+            "\n",
+            "\t \t  \n",
+            "\t   # not a block\n",
+            "\t#p[raw=no] ho\n",
+            "\t \tha",
+        );
+
+        assert_eq!(
+            reindent(input, &raw_blocks()),
+            concat!(
+                "#p hi\n",
+                "  #listing[raw,lang=make] all:\n",
+                "    \tcc -o x x.c\n",
+                "\n",
+                "      \n",
+                "     # not a block\n",
+                "  #p[raw=no] ho\n",
+                "    ha",
+            )
+        );
+    }
+
+    #[test]
+    fn reindent_raw_keeps_tabs() {
+        let input = "#listing[raw]\n  all:\n  \tcc -o x x.c\n#p  a";
+        assert_eq!(reindent(input, &raw_blocks()), input);
+        assert_eq!(
+            reindent(input, &ParserOptions::default()),
+            "#listing[raw]\n  all:\n    cc -o x x.c\n#p  a"
+        );
+    }
+
+    #[test]
+    fn reindent_clears_blank_lines() {
+        assert_eq!(
+            reindent("#p hi\n   \n  ho", &ParserOptions::default()),
+            "#p hi\n\n  ho"
+        );
+    }
+}
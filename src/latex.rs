@@ -0,0 +1,292 @@
+use super::util::{is_block, text_content};
+use super::{CommentNode, ElementNode, Node, StringNode, Translator};
+
+use std::collections::HashMap;
+
+/// How the LaTeX translator renders an element.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LatexMapping {
+    /// A command taking the content as its argument, e.g. `\emph{…}`.
+    Command(String),
+
+    /// A command taking the value of the given attribute as its first
+    /// argument and the content as its second, e.g. `\href{url}{…}`.
+    CommandWithAttribute(String, String),
+
+    /// A command preceding the content, e.g. `\item …`.
+    Prefix(String),
+
+    /// An environment around the content, e.g. `\begin{quote}…\end{quote}`.
+    Environment(String),
+
+    /// An environment around the literal text content, without any escaping,
+    /// e.g. `\begin{verbatim}…\end{verbatim}`.
+    Verbatim(String),
+
+    /// A paragraph: the content followed by a blank line.
+    Paragraph,
+
+    /// The content only, as if the element were not there. A block with inline
+    /// content only is followed by a blank line, like a paragraph.
+    Children,
+}
+
+/// Translates D★Mark to LaTeX.
+///
+/// Elements are rendered according to the `elements` table. Sections nest:
+/// a heading element inside _n_ section elements is rendered with the _n_th
+/// heading command, so that the same source can be used at any level.
+///
+/// The context is the current section depth, which starts at zero.
+///
+/// # Examples
+///
+/// ```
+/// use d_mark::{LatexTranslator, Parser};
+///
+/// let parsed = Parser::call("#section\n  #header Intro\n  #p 100%% %em{fun}")
+///     .expect("parsing failed");
+/// let latex = LatexTranslator::new().translate_document(&parsed);
+/// assert_eq!(latex, "\\section{Intro}\n\n100\\% \\emph{fun}\n");
+/// ```
+pub struct LatexTranslator {
+    pub elements: HashMap<String, LatexMapping>,
+    pub unknown_element: LatexMapping,
+    pub section_element: String,
+    pub heading_element: String,
+    pub heading_commands: Vec<String>,
+}
+
+impl Default for LatexTranslator {
+    fn default() -> Self {
+        let mut elements = HashMap::new();
+        elements.insert("p".into(), LatexMapping::Paragraph);
+        elements.insert("em".into(), LatexMapping::Command("emph".into()));
+        elements.insert("strong".into(), LatexMapping::Command("textbf".into()));
+        elements.insert("code".into(), LatexMapping::Command("texttt".into()));
+        elements.insert(
+            "link".into(),
+            LatexMapping::CommandWithAttribute("href".into(), "target".into()),
+        );
+        elements.insert("listing".into(), LatexMapping::Verbatim("verbatim".into()));
+        elements.insert("ul".into(), LatexMapping::Environment("itemize".into()));
+        elements.insert("ol".into(), LatexMapping::Environment("enumerate".into()));
+        elements.insert("li".into(), LatexMapping::Prefix("item".into()));
+        elements.insert("blockquote".into(), LatexMapping::Environment("quote".into()));
+
+        LatexTranslator {
+            elements,
+            unknown_element: LatexMapping::Children,
+            section_element: "section".into(),
+            heading_element: "header".into(),
+            heading_commands: vec![
+                "section".into(),
+                "subsection".into(),
+                "subsubsection".into(),
+                "paragraph".into(),
+                "subparagraph".into(),
+            ],
+        }
+    }
+}
+
+impl LatexTranslator {
+    pub fn new() -> Self {
+        LatexTranslator::default()
+    }
+
+    /// Translate a whole document, as returned by the parser.
+    pub fn translate_document(&self, nodes: &[Node]) -> String {
+        let res = self.translate_children(nodes, 0);
+        let res = res.trim_end();
+        if res.is_empty() {
+            String::new()
+        } else {
+            format!("{}\n", res)
+        }
+    }
+
+    fn translate_children(&self, nodes: &[Node], depth: usize) -> String {
+        let mut res = String::new();
+        for node in nodes {
+            let translated = self.translate(node, depth);
+            // Text before a block is a paragraph of its own
+            if is_block(node) && !res.is_empty() && !res.ends_with('\n') {
+                res = format!("{}\n\n", res.trim_end());
+            }
+            res.push_str(&translated);
+        }
+        res
+    }
+
+    fn heading_command(&self, depth: usize) -> &str {
+        let idx = depth.saturating_sub(1).min(self.heading_commands.len().saturating_sub(1));
+        self.heading_commands.get(idx).map_or("section", |c| c.as_ref())
+    }
+}
+
+impl Translator<String, usize> for LatexTranslator {
+    fn translate_element(&self, node: &ElementNode, depth: usize) -> String {
+        if node.name == self.section_element {
+            return self.translate_children(&node.children, depth + 1);
+        }
+
+        if node.name == self.heading_element {
+            return format!(
+                "\\{}{{{}}}\n\n",
+                self.heading_command(depth),
+                self.translate_children(&node.children, depth).trim()
+            );
+        }
+
+        let mapping = self
+            .elements
+            .get(node.name.as_ref())
+            .unwrap_or(&self.unknown_element);
+
+        match mapping {
+            LatexMapping::Command(name) => format!(
+                "\\{}{{{}}}",
+                name,
+                self.translate_children(&node.children, depth)
+            ),
+            LatexMapping::CommandWithAttribute(name, attribute) => format!(
+                "\\{}{{{}}}{{{}}}",
+                name,
+                escape(node.attributes.get(attribute).map_or("", |v| v.as_ref())),
+                self.translate_children(&node.children, depth)
+            ),
+            LatexMapping::Prefix(name) => format!(
+                "\\{} {}\n",
+                name,
+                self.translate_children(&node.children, depth).trim()
+            ),
+            LatexMapping::Environment(name) => format!(
+                "\\begin{{{}}}\n{}\n\\end{{{}}}\n\n",
+                name,
+                self.translate_children(&node.children, depth).trim(),
+                name
+            ),
+            LatexMapping::Verbatim(name) => format!(
+                "\\begin{{{}}}\n{}\n\\end{{{}}}\n\n",
+                name,
+                text_content(&node.children).trim_end_matches('\n'),
+                name
+            ),
+            LatexMapping::Paragraph => format!(
+                "{}\n\n",
+                self.translate_children(&node.children, depth).trim()
+            ),
+            // A block with inline content only is a paragraph of its own
+            LatexMapping::Children if node.block && !node.children.iter().any(is_block) => {
+                format!("{}\n\n", self.translate_children(&node.children, depth).trim())
+            }
+            LatexMapping::Children => self.translate_children(&node.children, depth),
+        }
+    }
+
+    fn translate_string(&self, node: &StringNode, _depth: usize) -> String {
+        escape(&node.content)
+    }
+
+    fn translate_comment(&self, node: &CommentNode, _depth: usize) -> String {
+        node.content
+            .lines()
+            .map(|l| format!("%{}\n", l))
+            .collect()
+    }
+}
+
+/// Escape characters that are special to TeX.
+fn escape(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => res.push_str("\\textbackslash{}"),
+            '~' => res.push_str("\\textasciitilde{}"),
+            '^' => res.push_str("\\textasciicircum{}"),
+            '{' | '}' | '$' | '&' | '#' | '%' | '_' => {
+                res.push('\\');
+                res.push(c);
+            }
+            _ => res.push(c),
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LatexMapping, LatexTranslator};
+    use {Parser, ParserOptions};
+
+    fn latex(s: &str) -> String {
+        let options = ParserOptions {
+            raw_blocks: true,
+            ..ParserOptions::default()
+        };
+        LatexTranslator::new().translate_document(&Parser::call_with_options(s, options).unwrap())
+    }
+
+    #[test]
+    fn nested_sections() {
+        assert_eq!(
+            latex("#section\n  #header foo\n  #section\n    #header bar\n    #p baz"),
+            "\\section{foo}\n\n\\subsection{bar}\n\nbaz\n"
+        );
+    }
+
+    #[test]
+    fn deep_sections_use_last_command() {
+        let mut input = String::new();
+        for i in 0..7 {
+            input.push_str(&"  ".repeat(i));
+            input.push_str("#section\n");
+        }
+        input.push_str(&"  ".repeat(7));
+        input.push_str("#header deep");
+        assert_eq!(latex(&input), "\\subparagraph{deep}\n");
+    }
+
+    #[test]
+    fn escaping() {
+        assert_eq!(
+            latex("#p $5 & 10%% of a_b #1 {x%} ~ ^ \\"),
+            "\\$5 \\& 10\\% of a\\_b \\#1 \\{x\\} \\textasciitilde{} \\textasciicircum{} \\textbackslash{}\n"
+        );
+    }
+
+    #[test]
+    fn default_elements() {
+        assert_eq!(
+            latex("#p See %link[target=http://a.org/#x]{%strong{this}}.\n#ul\n  #li one\n  #li two\n#listing[raw]\n  a_b{}"),
+            "See \\href{http://a.org/\\#x}{\\textbf{this}}.\n\n\\begin{itemize}\n\\item one\n\\item two\n\\end{itemize}\n\n\\begin{verbatim}\na_b{}\n\\end{verbatim}\n"
+        );
+    }
+
+    #[test]
+    fn unknown_blocks() {
+        assert_eq!(
+            latex("#note a\n#note b\n#p c\n  #aside d %kbd{e}"),
+            "a\n\nb\n\nc\n\nd e\n"
+        );
+    }
+
+    #[test]
+    fn custom_table() {
+        let mut translator = LatexTranslator::new();
+        translator
+            .elements
+            .insert("kbd".into(), LatexMapping::Command("keys".into()));
+        translator
+            .elements
+            .insert("note".into(), LatexMapping::Environment("tcolorbox".into()));
+        translator.unknown_element = LatexMapping::Command("unknown".into());
+
+        let parsed = Parser::call("#note\n  #p Press %kbd{Esc} %x{y}.").unwrap();
+        assert_eq!(
+            translator.translate_document(&parsed),
+            "\\begin{tcolorbox}\nPress \\keys{Esc} \\unknown{y}.\n\\end{tcolorbox}\n"
+        );
+    }
+}
@@ -10,30 +10,146 @@
 //! println!("{:#?}", parsed);
 //! ```
 
+#[cfg(feature = "derive")]
+extern crate d_mark_derive;
+#[cfg(feature = "markdown-import")]
+extern crate pulldown_cmark;
+#[cfg(feature = "xml-import")]
+extern crate roxmltree;
+#[cfg(feature = "deserialize")]
+extern crate serde;
+#[cfg(all(test, feature = "deserialize"))]
+#[macro_use]
+extern crate serde_derive;
+extern crate unicode_xid;
+
+#[cfg(feature = "deserialize")]
+mod de;
+mod dmark;
+mod include;
+mod indentation;
+mod latex;
+mod man;
+mod markdown;
+#[cfg(feature = "markdown-import")]
+mod markdown_import;
 mod parser;
+mod plain_text;
+mod profile;
+mod registry;
+mod source_map;
+mod toc;
 mod translator;
+mod traversal;
+mod typed;
 mod util;
+mod xml;
+#[cfg(feature = "xml-import")]
+mod xml_import;
 
 use std::borrow::Cow;
 use std::collections::HashMap;
 
-pub use parser::Parser;
-pub use translator::Translator;
+#[cfg(feature = "derive")]
+pub use d_mark_derive::FromNode;
+#[cfg(feature = "deserialize")]
+pub use de::{from_nodes, from_str, DeserializeError, TEXT_FIELD, VALUE_FIELD};
+pub use dmark::{DmarkTranslator, DEFAULT_BLOCK_ELEMENTS};
+pub use include::{FileLoader, IncludeError, IncludeLoader, IncludeResolver};
+pub use indentation::reindent;
+pub use latex::{LatexMapping, LatexTranslator};
+pub use man::ManTranslator;
+pub use markdown::{ElementFn, MarkdownTranslator, UnknownElement};
+#[cfg(feature = "markdown-import")]
+pub use markdown_import::import_markdown;
+pub use parser::{
+    Error as ParseError, ErrorWithContext, Parser, ParserOptions, DEFAULT_MAX_DEPTH, RAW_ATTRIBUTE,
+};
+pub use plain_text::PlainTextTranslator;
+pub use profile::ProfileFilter;
+pub use registry::{ElementHandler, ElementPredicate, TranslatorRegistry};
+pub use source_map::{FileId, SourceError, SourceMap, Span};
+pub use toc::{Toc, TocBuilder, TocEntry};
+pub use translator::{
+    ShallowClone, TranslationError, Translator, TryTranslator, WriteTranslator,
+};
+pub use traversal::TraversalContext;
+pub use typed::{parse_attribute, require_attribute, FromChildren, TypedError, TypedTranslator};
+pub use xml::{XmlElement, XmlTranslator, DOCBOOK_NAMESPACE};
+#[cfg(feature = "xml-import")]
+pub use xml_import::{XmlImportError, XmlImporter};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct ElementNode {
     name: Cow<'static, str>,
     attributes: HashMap<String, String>,
     children: Vec<Node>,
+    block: bool,
+}
+
+impl ElementNode {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn attributes(&self) -> &HashMap<String, String> {
+        &self.attributes
+    }
+
+    pub fn children(&self) -> &[Node] {
+        &self.children
+    }
+
+    /// Whether the element is a block, written as `#name` on a line of its
+    /// own, rather than inline, as `%name{…}`.
+    pub fn is_block(&self) -> bool {
+        self.block
+    }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct StringNode {
     content: Cow<'static, str>,
 }
 
-#[derive(Debug, PartialEq)]
+impl StringNode {
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommentNode {
+    content: Cow<'static, str>,
+}
+
+impl CommentNode {
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum Node {
     Element(ElementNode),
     String(StringNode),
+    Comment(CommentNode),
+}
+
+impl From<ElementNode> for Node {
+    fn from(node: ElementNode) -> Node {
+        Node::Element(node)
+    }
+}
+
+impl From<StringNode> for Node {
+    fn from(node: StringNode) -> Node {
+        Node::String(node)
+    }
+}
+
+impl From<CommentNode> for Node {
+    fn from(node: CommentNode) -> Node {
+        Node::Comment(node)
+    }
 }
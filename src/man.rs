@@ -0,0 +1,324 @@
+use super::util::{is_block, text_content};
+use super::{CommentNode, ElementNode, Node, StringNode, Translator};
+
+/// Translates D★Mark to a `man(7)` page.
+///
+/// The known elements are:
+///
+/// * `h1` and `h2` (section headings), and `h3` to `h6` (subsection headings)
+/// * `section`, which nests; a `header` directly in a top-level `section` is a
+///   section heading, and one in a nested `section` is a subsection heading
+/// * `p` (paragraphs) and `listing` (verbatim blocks)
+/// * `dl` with `dt` and `dd` (tagged paragraphs, e.g. for lists of options)
+/// * `ul` and `ol` with `li` (bulleted and numbered lists)
+/// * `em` (italic), `strong` and `code` (bold), and `link` (with a `target`)
+///
+/// Other elements are rendered as their content only, as a paragraph of its
+/// own if they are blocks.
+///
+/// The context is the current section depth, which starts at zero.
+///
+/// # Examples
+///
+/// ```
+/// use d_mark::{ManTranslator, Parser};
+///
+/// let parsed = Parser::call("#h1 NAME\n#p d-mark %em{parses} D★Mark")
+///     .expect("parsing failed");
+/// let man = ManTranslator::new("d-mark", "1").translate_document(&parsed);
+/// assert_eq!(man, ".TH \"D\\-MARK\" \"1\"\n.SH NAME\n.PP\nd\\-mark \\fIparses\\fP D★Mark\n");
+/// ```
+pub struct ManTranslator {
+    pub title: String,
+    pub section: String,
+}
+
+impl ManTranslator {
+    pub fn new(title: &str, section: &str) -> Self {
+        ManTranslator {
+            title: title.into(),
+            section: section.into(),
+        }
+    }
+
+    /// Translate a whole document, as returned by the parser, including the
+    /// `.TH` title line.
+    pub fn translate_document(&self, nodes: &[Node]) -> String {
+        let mut res = format!(
+            ".TH \"{}\" \"{}\"\n",
+            escape_argument(&self.title.to_uppercase()),
+            escape_argument(&self.section)
+        );
+        let body = self.translate_blocks(nodes, 0);
+        if !body.is_empty() {
+            res.push_str(&body);
+            res.push('\n');
+        }
+        res
+    }
+
+    fn translate_inline(&self, nodes: &[Node], depth: usize) -> String {
+        nodes.iter().map(|n| self.translate(n, depth)).collect()
+    }
+
+    /// Translate nodes into blocks, each starting with a request on a line of
+    /// its own. Runs of inline nodes become filled text.
+    fn translate_blocks<'a, I>(&self, nodes: I, depth: usize) -> String
+    where
+        I: IntoIterator<Item = &'a Node>,
+    {
+        let mut blocks = vec![];
+        let mut inline = String::new();
+
+        for node in nodes {
+            if is_block(node) {
+                blocks.push(text_lines(&inline));
+                inline.clear();
+                blocks.push(self.translate(node, depth));
+            } else {
+                inline.push_str(&self.translate(node, depth));
+            }
+        }
+        blocks.push(text_lines(&inline));
+
+        blocks.retain(|b| !b.is_empty());
+        blocks.join("\n")
+    }
+
+    fn translate_paragraph(&self, node: &ElementNode, depth: usize) -> String {
+        format!(".PP\n{}", self.translate_blocks(&node.children, depth))
+    }
+
+    /// Translate the description of a tagged paragraph. The first paragraph
+    /// follows the tag directly, and later ones are indented like it with
+    /// `.IP`, since `.PP` would end the indentation.
+    fn translate_description(&self, node: &ElementNode, depth: usize) -> String {
+        let mut blocks = vec![];
+        let mut inline = String::new();
+
+        for child in &node.children {
+            match child {
+                Node::Element(n) if n.name == "p" => {
+                    blocks.push(text_lines(&inline));
+                    inline.clear();
+                    blocks.push(self.translate_blocks(&n.children, depth));
+                }
+                _ if is_block(child) => {
+                    blocks.push(text_lines(&inline));
+                    inline.clear();
+                    blocks.push(self.translate(child, depth));
+                }
+                _ => inline.push_str(&self.translate(child, depth)),
+            }
+        }
+        blocks.push(text_lines(&inline));
+
+        blocks.retain(|b| !b.is_empty());
+        blocks
+            .iter()
+            .enumerate()
+            .map(|(i, b)| {
+                if i == 0 || b.starts_with('.') {
+                    b.clone()
+                } else {
+                    format!(".IP\n{}", b)
+                }
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    fn translate_heading(&self, node: &ElementNode, macro_name: &str, depth: usize) -> String {
+        format!(
+            ".{} {}",
+            macro_name,
+            self.translate_inline(&node.children, depth)
+                .split_whitespace()
+                .collect::<Vec<&str>>()
+                .join(" ")
+        )
+    }
+
+    fn translate_list(&self, node: &ElementNode, ordered: bool, depth: usize) -> String {
+        node.children
+            .iter()
+            .filter_map(|c| match c {
+                Node::Element(n) => Some(n),
+                _ => None,
+            }).enumerate()
+            .map(|(i, item)| {
+                let tag = if ordered {
+                    format!(".IP {}. 4", i + 1)
+                } else {
+                    ".IP \\(bu 2".to_string()
+                };
+                format!("{}\n{}", tag, self.translate_list_item(item, depth))
+            }).collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// Translate the content of a list item, indenting nested lists.
+    fn translate_list_item(&self, node: &ElementNode, depth: usize) -> String {
+        let mut blocks = vec![];
+        let mut rest = vec![];
+
+        for child in &node.children {
+            match child {
+                Node::Element(n) if n.name == "ul" || n.name == "ol" => {
+                    blocks.push(self.translate_blocks(rest.drain(..), depth));
+                    blocks.push(format!(".RS\n{}\n.RE", self.translate(child, depth)));
+                }
+                _ => rest.push(child),
+            }
+        }
+        blocks.push(self.translate_blocks(rest, depth));
+
+        blocks.retain(|b| !b.is_empty());
+        blocks.join("\n")
+    }
+}
+
+impl Translator<String, usize> for ManTranslator {
+    fn translate_element(&self, node: &ElementNode, depth: usize) -> String {
+        match node.name.as_ref() {
+            "section" => self.translate_blocks(&node.children, depth + 1),
+            "header" if depth <= 1 => self.translate_heading(node, "SH", depth),
+            "header" => self.translate_heading(node, "SS", depth),
+            "h1" | "h2" => self.translate_heading(node, "SH", depth),
+            "h3" | "h4" | "h5" | "h6" => self.translate_heading(node, "SS", depth),
+            "p" => self.translate_paragraph(node, depth),
+            "listing" => {
+                let content = text_content(&node.children);
+                let lines = content
+                    .trim_end_matches('\n')
+                    .lines()
+                    .map(|l| escape_line_start(&escape(l)))
+                    .collect::<Vec<String>>()
+                    .join("\n");
+                format!(".PP\n.RS 4\n.nf\n{}\n.fi\n.RE", lines)
+            }
+            "dl" => self.translate_blocks(&node.children, depth),
+            "dt" => format!(".TP\n{}", text_lines(&self.translate_inline(&node.children, depth))),
+            "dd" => self.translate_description(node, depth),
+            "ul" => self.translate_list(node, false, depth),
+            "ol" => self.translate_list(node, true, depth),
+            "li" => format!(".IP \\(bu 2\n{}", self.translate_list_item(node, depth)),
+            "em" => format!("\\fI{}\\fP", self.translate_inline(&node.children, depth)),
+            "strong" | "code" => format!("\\fB{}\\fP", self.translate_inline(&node.children, depth)),
+            "link" => {
+                let text = self.translate_inline(&node.children, depth);
+                match node.attributes.get("target") {
+                    Some(target) => format!("{} <{}>", text, escape(target)),
+                    None => text,
+                }
+            }
+            _ if node.children.iter().any(is_block) => {
+                self.translate_blocks(&node.children, depth)
+            }
+            _ if node.block => self.translate_paragraph(node, depth),
+            _ => self.translate_inline(&node.children, depth),
+        }
+    }
+
+    fn translate_string(&self, node: &StringNode, _depth: usize) -> String {
+        escape(&node.content)
+    }
+
+    fn translate_comment(&self, _node: &CommentNode, _depth: usize) -> String {
+        String::new()
+    }
+}
+
+/// Turn inline content into text lines: trim leading whitespace, which would
+/// otherwise cause a break, drop empty lines, and protect lines that would be
+/// taken as requests.
+fn text_lines(s: &str) -> String {
+    s.lines()
+        .map(str::trim_start)
+        .filter(|l| !l.is_empty())
+        .map(escape_line_start)
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn escape_line_start(line: &str) -> String {
+    if line.starts_with('.') || line.starts_with('\'') {
+        format!("\\&{}", line)
+    } else {
+        line.to_string()
+    }
+}
+
+/// Escape a quoted argument of a request.
+fn escape_argument(s: &str) -> String {
+    escape(s).replace('"', "\\(dq")
+}
+
+/// Escape characters that are special to roff within a line.
+fn escape(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => res.push_str("\\e"),
+            '-' => res.push_str("\\-"),
+            _ => res.push(c),
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ManTranslator;
+    use Parser;
+
+    fn man(s: &str) -> String {
+        ManTranslator::new("tool", "1").translate_document(&Parser::call(s).unwrap())
+    }
+
+    #[test]
+    fn sections_and_paragraphs() {
+        assert_eq!(
+            man("#section\n  #header Name\n  #p tool %strong{does} things\n  #section\n    #header More\n    #p .hidden\n      'quoted"),
+            ".TH \"TOOL\" \"1\"\n.SH Name\n.PP\ntool \\fBdoes\\fP things\n.SS More\n.PP\n\\&.hidden\n\\&'quoted\n"
+        );
+    }
+
+    #[test]
+    fn options_list() {
+        assert_eq!(
+            man("#dl\n  #dt %code{--verbose}, %code{-v}\n  #dd\n    #p Print more.\n    #p Twice for all.\n  #dt %code{--out} %em{file}\n  #dd Write to %em{file}."),
+            ".TH \"TOOL\" \"1\"\n.TP\n\\fB\\-\\-verbose\\fP, \\fB\\-v\\fP\nPrint more.\n.IP\nTwice for all.\n.TP\n\\fB\\-\\-out\\fP \\fIfile\\fP\nWrite to \\fIfile\\fP.\n"
+        );
+    }
+
+    #[test]
+    fn unknown_blocks() {
+        assert_eq!(
+            man("#note a\n#note b\n#p c\n  #aside d %kbd{e}"),
+            ".TH \"TOOL\" \"1\"\n.PP\na\n.PP\nb\n.PP\nc\n.PP\nd e\n"
+        );
+    }
+
+    #[test]
+    fn title_quotes() {
+        let man = ManTranslator::new("say \"hi\"", "1\"").translate_document(&[]);
+        assert_eq!(man, ".TH \"SAY \\(dqHI\\(dq\" \"1\\(dq\"\n");
+    }
+
+    #[test]
+    fn lists() {
+        assert_eq!(
+            man("#ul\n  #li one\n  #li two\n    #ol\n      #li three"),
+            ".TH \"TOOL\" \"1\"\n.IP \\(bu 2\none\n.IP \\(bu 2\ntwo\n.RS\n.IP 1. 4\nthree\n.RE\n"
+        );
+    }
+
+    #[test]
+    fn listing_escapes() {
+        assert_eq!(
+            man("#listing[raw]\n  .PP\n  echo \"a\\b\" -n"),
+            ".TH \"TOOL\" \"1\"\n.PP\n.RS 4\n.nf\n\\&.PP\necho \"a\\eb\" \\-n\n.fi\n.RE\n"
+        );
+    }
+}
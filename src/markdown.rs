@@ -0,0 +1,388 @@
+use super::util::{is_block, text_content};
+use super::{CommentNode, ElementNode, Node, StringNode, Translator};
+
+/// A function that renders an element, given the element and its translated
+/// children.
+pub type ElementFn = Box<dyn Fn(&ElementNode, &str) -> String>;
+
+/// What the Markdown translator does with elements it does not know.
+pub enum UnknownElement {
+    /// Render the children only, as if the element were not there.
+    Children,
+
+    /// Render the element as inline HTML, e.g. `<kbd>Esc</kbd>`.
+    Html,
+
+    /// Leave out the element, including its children.
+    Omit,
+
+    /// Render the element with the given function, which receives the element
+    /// and its translated children.
+    Custom(ElementFn),
+}
+
+/// Translates D★Mark to CommonMark.
+///
+/// The known elements are `p`, `h1` to `h6`, `em`, `strong`, `code`, `link`
+/// (with a `target` attribute), `listing` (with an optional `lang` attribute),
+/// `ul`, `ol`, `li` and `blockquote`.
+///
+/// # Examples
+///
+/// ```
+/// use d_mark::{MarkdownTranslator, Parser};
+///
+/// let parsed = Parser::call("#p I %em{love} Rust!").expect("parsing failed");
+/// let markdown = MarkdownTranslator::new().translate_document(&parsed);
+/// assert_eq!(markdown, "I *love* Rust!\n");
+/// ```
+pub struct MarkdownTranslator {
+    pub unknown_element: UnknownElement,
+}
+
+impl Default for MarkdownTranslator {
+    fn default() -> Self {
+        MarkdownTranslator {
+            unknown_element: UnknownElement::Children,
+        }
+    }
+}
+
+impl MarkdownTranslator {
+    pub fn new() -> Self {
+        MarkdownTranslator::default()
+    }
+
+    /// Translate a whole document, as returned by the parser.
+    pub fn translate_document(&self, nodes: &[Node]) -> String {
+        let mut res = self.translate_blocks(nodes);
+        if !res.is_empty() {
+            res.push('\n');
+        }
+        res
+    }
+
+    fn translate_children(&self, nodes: &[Node]) -> String {
+        if nodes.iter().any(is_block) {
+            self.translate_blocks(nodes)
+        } else {
+            self.translate_inline(nodes)
+        }
+    }
+
+    fn translate_inline(&self, nodes: &[Node]) -> String {
+        nodes.iter().map(|n| self.translate(n, ())).collect()
+    }
+
+    /// Translate nodes into blocks separated by blank lines. Runs of inline
+    /// nodes become paragraphs.
+    fn translate_blocks(&self, nodes: &[Node]) -> String {
+        let mut blocks = vec![];
+        let mut inline = String::new();
+
+        for node in nodes {
+            if is_block(node) {
+                push_paragraph(&mut blocks, &mut inline);
+                blocks.push(self.translate(node, ()));
+            } else {
+                inline.push_str(&self.translate(node, ()));
+            }
+        }
+        push_paragraph(&mut blocks, &mut inline);
+
+        blocks.retain(|b| !b.is_empty());
+        blocks.join("\n\n")
+    }
+
+    fn translate_list(&self, node: &ElementNode, ordered: bool) -> String {
+        node.children
+            .iter()
+            .filter_map(|c| match c {
+                Node::Element(n) => Some(n),
+                _ => None,
+            }).enumerate()
+            .map(|(i, item)| {
+                let marker = if ordered {
+                    format!("{}. ", i + 1)
+                } else {
+                    "- ".to_string()
+                };
+                let content = if item.name == "li" {
+                    self.translate_blocks(&item.children)
+                } else {
+                    self.translate_element(item, ())
+                };
+                list_item(&content, &marker)
+            }).collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    fn translate_unknown(&self, node: &ElementNode) -> String {
+        match self.unknown_element {
+            UnknownElement::Children => self.translate_children(&node.children),
+            UnknownElement::Html => {
+                let mut attributes: Vec<_> = node.attributes.iter().collect();
+                attributes.sort();
+                format!(
+                    "<{}{}>{}</{}>",
+                    node.name,
+                    attributes
+                        .iter()
+                        .map(|(k, v)| format!(" {}=\"{}\"", k, escape_html_attribute(v)))
+                        .collect::<String>(),
+                    self.translate_inline(&node.children),
+                    node.name,
+                )
+            }
+            UnknownElement::Omit => String::new(),
+            UnknownElement::Custom(ref f) => f(node, &self.translate_children(&node.children)),
+        }
+    }
+}
+
+impl Translator<String, ()> for MarkdownTranslator {
+    fn translate_element(&self, node: &ElementNode, _context: ()) -> String {
+        match node.name.as_ref() {
+            "p" => self.translate_blocks(&node.children),
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                let level = node.name[1..].parse().unwrap_or(1);
+                format!(
+                    "{} {}",
+                    "#".repeat(level),
+                    self.translate_inline(&node.children).replace('\n', " ")
+                )
+            }
+            "em" => format!("*{}*", self.translate_inline(&node.children)),
+            "strong" => format!("**{}**", self.translate_inline(&node.children)),
+            "code" => code_span(&text_content(&node.children)),
+            "link" => {
+                let text = self.translate_inline(&node.children);
+                match node.attributes.get("target") {
+                    Some(target) => format!("[{}]({})", text, link_destination(target)),
+                    None => text,
+                }
+            }
+            "listing" => code_block(
+                &text_content(&node.children),
+                node.attributes.get("lang").map_or("", |l| l.as_ref()),
+            ),
+            "ul" => self.translate_list(node, false),
+            "ol" => self.translate_list(node, true),
+            "li" => list_item(&self.translate_blocks(&node.children), "- "),
+            "blockquote" => self
+                .translate_blocks(&node.children)
+                .lines()
+                .map(|l| if l.is_empty() { ">".to_string() } else { format!("> {}", l) })
+                .collect::<Vec<String>>()
+                .join("\n"),
+            _ => self.translate_unknown(node),
+        }
+    }
+
+    fn translate_string(&self, node: &StringNode, _context: ()) -> String {
+        escape(&node.content)
+    }
+
+    fn translate_comment(&self, node: &CommentNode, _context: ()) -> String {
+        format!("<!--{}-->", node.content.replace("--", "- -"))
+    }
+}
+
+fn push_paragraph(blocks: &mut Vec<String>, inline: &mut String) {
+    let paragraph = inline
+        .trim_matches('\n')
+        .lines()
+        .map(|l| escape_line_start(l.trim_start()))
+        .collect::<Vec<String>>()
+        .join("\n");
+    if !paragraph.is_empty() {
+        blocks.push(paragraph);
+    }
+    inline.clear();
+}
+
+fn list_item(content: &str, marker: &str) -> String {
+    let indent = " ".repeat(marker.len());
+    let mut res = marker.to_string();
+    for (i, line) in content.lines().enumerate() {
+        if i > 0 {
+            res.push('\n');
+            if !line.is_empty() {
+                res.push_str(&indent);
+            }
+        }
+        res.push_str(line);
+    }
+    res
+}
+
+/// Escape characters that could be taken as Markdown syntax anywhere in a line.
+fn escape(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        if "\\`*_[]<>#|&~".contains(c) {
+            res.push('\\');
+        }
+        res.push(c);
+    }
+    res
+}
+
+/// Escape the start of a line that would otherwise be taken as a list item or
+/// a setext heading underline.
+fn escape_line_start(line: &str) -> String {
+    let digits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+    let punct_idx = match line[digits..].chars().next() {
+        Some('-') | Some('+') | Some('=') if digits == 0 => Some(0),
+        Some('.') | Some(')') if digits > 0 => Some(digits),
+        _ => None,
+    };
+
+    match punct_idx {
+        Some(idx) => format!("{}\\{}", &line[..idx], &line[idx..]),
+        None => line.to_string(),
+    }
+}
+
+fn longest_backtick_run(s: &str) -> usize {
+    s.split(|c| c != '`').map(str::len).max().unwrap_or(0)
+}
+
+fn code_span(s: &str) -> String {
+    if s.is_empty() {
+        return String::new();
+    }
+
+    let fence = "`".repeat(longest_backtick_run(s) + 1);
+    let pad = if s.starts_with('`') || s.ends_with('`') || (s.starts_with(' ') && s.ends_with(' ')) {
+        " "
+    } else {
+        ""
+    };
+    format!("{}{}{}{}{}", fence, pad, s.replace('\n', " "), pad, fence)
+}
+
+fn code_block(s: &str, lang: &str) -> String {
+    let fence = "`".repeat((longest_backtick_run(s) + 1).max(3));
+    let s = s.trim_end_matches('\n');
+    if s.is_empty() {
+        format!("{}{}\n{}", fence, lang, fence)
+    } else {
+        format!("{}{}\n{}\n{}", fence, lang, s, fence)
+    }
+}
+
+fn link_destination(target: &str) -> String {
+    if target.contains(|c| " ()<>".contains(c)) {
+        format!("<{}>", target.replace('<', "%3C").replace('>', "%3E"))
+    } else {
+        target.to_string()
+    }
+}
+
+fn escape_html_attribute(s: &str) -> String {
+    s.replace('&', "&amp;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MarkdownTranslator, UnknownElement};
+    use Parser;
+
+    fn md(s: &str) -> String {
+        MarkdownTranslator::new().translate_document(&Parser::call(s).unwrap())
+    }
+
+    #[test]
+    fn paragraphs_and_headings() {
+        assert_eq!(
+            md("#h1 Title\n#p Some %em{nice} and %strong{bold}\n  text.\n#h3 Sub"),
+            "# Title\n\nSome *nice* and **bold**\ntext.\n\n### Sub\n"
+        );
+    }
+
+    #[test]
+    fn escaping() {
+        assert_eq!(
+            md("#p a*b_c [d] <e> %#f\n  - g\n  1. h\n  2024 i"),
+            "a\\*b\\_c \\[d\\] \\<e\\> \\#f\n\\- g\n1\\. h\n2024 i\n"
+        );
+    }
+
+    #[test]
+    fn code_and_links() {
+        assert_eq!(
+            md("#p Run %code{a `b` c}, see %link[target=http://x.org/a b]{%em{docs}}."),
+            "Run ``a `b` c``, see [*docs*](<http://x.org/a b>).\n"
+        );
+    }
+
+    #[test]
+    fn listing() {
+        assert_eq!(
+            md("#listing[raw,lang=rust]\n  let x = \"```\";\n\n    y();"),
+            "````rust\nlet x = \"```\";\n\n  y();\n````\n"
+        );
+    }
+
+    #[test]
+    fn lists() {
+        assert_eq!(
+            md("#ul\n  #li one\n  #li\n    #p two\n    #ol\n      #li a\n      #li b\n#ol\n  #li x"),
+            "- one\n- two\n\n  1. a\n  2. b\n\n1. x\n"
+        );
+    }
+
+    #[test]
+    fn blockquote() {
+        assert_eq!(
+            md("#blockquote\n  #p a\n  #p b"),
+            "> a\n>\n> b\n"
+        );
+    }
+
+    #[test]
+    fn unknown_children() {
+        assert_eq!(
+            md("#section\n  #p a %kbd{Esc}\n  #p b"),
+            "a Esc\n\nb\n"
+        );
+    }
+
+    #[test]
+    fn unknown_blocks() {
+        assert_eq!(
+            md("#note a\n#note b\n#p c\n  #aside d %kbd{e}"),
+            "a\n\nb\n\nc\n\nd e\n"
+        );
+    }
+
+    #[test]
+    fn unknown_html() {
+        let translator = MarkdownTranslator {
+            unknown_element: UnknownElement::Html,
+        };
+        let parsed = Parser::call("#p Press %kbd[class=k]{Esc}.").unwrap();
+        assert_eq!(
+            translator.translate_document(&parsed),
+            "Press <kbd class=\"k\">Esc</kbd>.\n"
+        );
+    }
+
+    #[test]
+    fn unknown_omit_and_custom() {
+        let parsed = Parser::call("#p a%note{b}c\n#todo x").unwrap();
+
+        let translator = MarkdownTranslator {
+            unknown_element: UnknownElement::Omit,
+        };
+        assert_eq!(translator.translate_document(&parsed), "ac\n");
+
+        let translator = MarkdownTranslator {
+            unknown_element: UnknownElement::Custom(Box::new(|node, content| {
+                format!("{}:{}", node.name(), content)
+            })),
+        };
+        assert_eq!(translator.translate_document(&parsed), "anote:bc\n\ntodo:x\n");
+    }
+}
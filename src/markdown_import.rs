@@ -0,0 +1,210 @@
+use super::{ElementNode, Node, StringNode, RAW_ATTRIBUTE};
+
+use pulldown_cmark::{CodeBlockKind, Event, Parser as MarkdownParser, Tag, TagEnd};
+use std::collections::HashMap;
+
+/// Reads CommonMark into a node tree, using the elements that the
+/// [`MarkdownTranslator`](struct.MarkdownTranslator.html) knows, so that the
+/// result can be written out as D★Mark with
+/// [`DmarkTranslator`](struct.DmarkTranslator.html).
+///
+/// Besides those, thematic breaks become `hr`, hard line breaks `br`, images
+/// `image` (with a `src` attribute, and the alternative text as content), and
+/// HTML blocks raw `html` blocks. Inline HTML is kept as text.
+///
+/// # Examples
+///
+/// ```
+/// use d_mark::{import_markdown, DmarkTranslator};
+///
+/// let nodes = import_markdown("# Hello\n\nI *love* [Rust](https://www.rust-lang.org/)!\n");
+/// let dmark = DmarkTranslator::new().translate_document(&nodes);
+/// assert_eq!(
+///     dmark,
+///     "#h1 Hello\n#p I %em{love} %link[target=https://www.rust-lang.org/]{Rust}!\n"
+/// );
+/// ```
+pub fn import_markdown(s: &str) -> Vec<Node> {
+    let mut stack: Vec<ElementNode> = vec![];
+    let mut nodes = vec![];
+
+    for event in MarkdownParser::new(s) {
+        match event {
+            Event::Start(tag) => stack.push(element_for(tag)),
+            Event::End(tag) => {
+                let mut element = stack.pop().expect("unbalanced Markdown events");
+                if tag == TagEnd::CodeBlock || tag == TagEnd::HtmlBlock {
+                    trim_final_newline(&mut element);
+                }
+                push_node(&mut stack, &mut nodes, Node::Element(element));
+            }
+            Event::Text(text) | Event::Html(text) | Event::InlineHtml(text) => {
+                push_text(&mut stack, &mut nodes, &text)
+            }
+            Event::Code(text) => {
+                let code = element("code", vec![], vec![string(&text)], false);
+                push_node(&mut stack, &mut nodes, code);
+            }
+            Event::SoftBreak => push_text(&mut stack, &mut nodes, "\n"),
+            Event::HardBreak => {
+                push_node(&mut stack, &mut nodes, element("br", vec![], vec![], false));
+                push_text(&mut stack, &mut nodes, "\n");
+            }
+            Event::Rule => push_node(&mut stack, &mut nodes, element("hr", vec![], vec![], true)),
+            _ => (),
+        }
+    }
+
+    nodes
+}
+
+fn element_for(tag: Tag) -> ElementNode {
+    let block = !matches!(
+        tag,
+        Tag::Emphasis | Tag::Strong | Tag::Link { .. } | Tag::Image { .. }
+    );
+    let (name, attributes): (&str, Vec<(&str, String)>) = match tag {
+        Tag::Paragraph => ("p", vec![]),
+        Tag::Heading { level, .. } => return new_element(&level.to_string(), vec![], block),
+        Tag::BlockQuote(_) => ("blockquote", vec![]),
+        Tag::CodeBlock(CodeBlockKind::Fenced(info)) => {
+            let lang = info.split_whitespace().next().unwrap_or("").to_string();
+            let mut attributes = vec![(RAW_ATTRIBUTE, RAW_ATTRIBUTE.to_string())];
+            if !lang.is_empty() {
+                attributes.push(("lang", lang));
+            }
+            ("listing", attributes)
+        }
+        Tag::CodeBlock(CodeBlockKind::Indented) => {
+            ("listing", vec![(RAW_ATTRIBUTE, RAW_ATTRIBUTE.to_string())])
+        }
+        Tag::HtmlBlock => ("html", vec![(RAW_ATTRIBUTE, RAW_ATTRIBUTE.to_string())]),
+        Tag::List(Some(start)) if start != 1 => ("ol", vec![("start", start.to_string())]),
+        Tag::List(Some(_)) => ("ol", vec![]),
+        Tag::List(None) => ("ul", vec![]),
+        Tag::Item => ("li", vec![]),
+        Tag::Emphasis => ("em", vec![]),
+        Tag::Strong => ("strong", vec![]),
+        Tag::Link {
+            dest_url, title, ..
+        } => ("link", link_attributes("target", &dest_url, &title)),
+        Tag::Image {
+            dest_url, title, ..
+        } => ("image", link_attributes("src", &dest_url, &title)),
+        // Only reported for extensions, which are not enabled
+        _ => ("div", vec![]),
+    };
+    new_element(name, attributes, block)
+}
+
+fn link_attributes<'a>(url_key: &'a str, url: &str, title: &str) -> Vec<(&'a str, String)> {
+    let mut attributes = vec![(url_key, url.to_string())];
+    if !title.is_empty() {
+        attributes.push(("title", title.to_string()));
+    }
+    attributes
+}
+
+fn new_element(name: &str, attributes: Vec<(&str, String)>, block: bool) -> ElementNode {
+    ElementNode {
+        name: name.to_string().into(),
+        attributes: attributes
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect::<HashMap<String, String>>(),
+        children: vec![],
+        block,
+    }
+}
+
+fn element(
+    name: &str,
+    attributes: Vec<(&str, String)>,
+    children: Vec<Node>,
+    block: bool,
+) -> Node {
+    let mut element = new_element(name, attributes, block);
+    element.children = children;
+    Node::Element(element)
+}
+
+fn string(s: &str) -> Node {
+    Node::String(StringNode {
+        content: s.to_string().into(),
+    })
+}
+
+fn push_node(stack: &mut [ElementNode], nodes: &mut Vec<Node>, node: Node) {
+    match stack.last_mut() {
+        Some(parent) => parent.children.push(node),
+        None => nodes.push(node),
+    }
+}
+
+/// Add text, merging it with the text before it, if any.
+fn push_text(stack: &mut [ElementNode], nodes: &mut Vec<Node>, text: &str) {
+    let siblings = match stack.last_mut() {
+        Some(parent) => &mut parent.children,
+        None => nodes,
+    };
+    if let Some(Node::String(last)) = siblings.last_mut() {
+        last.content = format!("{}{}", last.content, text).into();
+        return;
+    }
+    siblings.push(string(text));
+}
+
+fn trim_final_newline(element: &mut ElementNode) {
+    if let Some(Node::String(last)) = element.children.last_mut() {
+        if last.content.ends_with('\n') {
+            let len = last.content.len() - 1;
+            last.content = last.content[..len].to_string().into();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::import_markdown;
+    use DmarkTranslator;
+
+    fn dmark(s: &str) -> String {
+        let translator = DmarkTranslator {
+            raw_blocks: true,
+            ..DmarkTranslator::new()
+        };
+        translator.translate_document(&import_markdown(s))
+    }
+
+    #[test]
+    fn blocks_and_inlines() {
+        assert_eq!(
+            dmark("## Title\n\nSome *nice* and **bold**\ntext, `a}b`  \nnext.\n\n---\n\n> quoted\n"),
+            "#h2 Title\n#p Some %em{nice} and %strong{bold}\n  text, %code{a%}b}%br{}\n  next.\n#hr\n#blockquote\n  #p quoted\n"
+        );
+    }
+
+    #[test]
+    fn code_blocks() {
+        assert_eq!(
+            dmark("```rust\nfn main() {\n    # x\n}\n```\n\n    indented\n"),
+            "#listing[lang=rust,raw] fn main() {\n      # x\n  }\n#listing[raw] indented\n"
+        );
+    }
+
+    #[test]
+    fn lists() {
+        assert_eq!(
+            dmark("- one\n- two\n  1. three\n  2. four\n\n3. a\n\n   b\n"),
+            "#ul\n  #li one\n  #li two\n    #ol\n      #li three\n      #li four\n#ol[start=3]\n  #li\n    #p a\n    #p b\n"
+        );
+    }
+
+    #[test]
+    fn links_and_images() {
+        assert_eq!(
+            dmark("[a, b](http://x.org/a%5D \"T\") ![alt](i.png)\n\n<div>\n<b>x</b>\n</div>\n"),
+            "#p %link[target=http://x.org/a%%5D,title=T]{a, b} %image[src=i.png]{alt}\n#html[raw] <div>\n  <b>x</b>\n  </div>\n"
+        );
+    }
+}
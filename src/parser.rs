@@ -1,9 +1,10 @@
-use super::util::FilterableResult;
-use super::{ElementNode, Node, StringNode};
+use super::{CommentNode, ElementNode, Node, StringNode};
 
 use std::collections::HashMap;
 use std::fmt;
 
+use unicode_xid::UnicodeXID;
+
 #[derive(Debug)]
 struct Pos {
     idx: usize,
@@ -32,7 +33,7 @@ impl Pos {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Error {
     /// An unexpected end-of-file was encountered.
     UnexpectedEOF,
@@ -61,8 +62,36 @@ pub enum Error {
     /// Expected a space, but none was found
     ExpectedSpace,
 
+    /// Expected a tab, but none was found
+    ExpectedTab,
+
+    /// Indentation with spaces used an odd number of spaces.
+    OddIndentation,
+
+    /// Indentation contained a tab where spaces were expected, or vice versa.
+    MixedTabsAndSpaces,
+
     /// Expected an name char, but none was found.
     InvalidCharInName,
+
+    /// Inline content was followed by something other than an end of line.
+    UnexpectedContentAfterInlineContent,
+
+    /// Expected a comma or a right bracket, ], after an attribute, but none
+    /// was found.
+    UnexpectedContentAfterAttribute,
+
+    /// The input is longer than the configured maximum input length.
+    InputTooLong,
+
+    /// Elements are nested deeper than the configured maximum depth.
+    DepthLimitExceeded,
+
+    /// An element has more attributes than the configured maximum.
+    TooManyAttributes,
+
+    /// The document has more nodes than the configured maximum.
+    TooManyNodes,
 }
 
 #[derive(Debug)]
@@ -73,6 +102,27 @@ pub struct ErrorWithContext<'a> {
     line1: Option<&'a str>,
 }
 
+impl<'a> ErrorWithContext<'a> {
+    pub fn error(&self) -> &Error {
+        &self.error
+    }
+
+    /// The line of the error, starting at 1.
+    pub fn line(&self) -> usize {
+        self.pos.line_nr + 1
+    }
+
+    /// The column of the error, starting at 1. It is that of the offending
+    /// character, or just past the end of the line or input.
+    pub fn column(&self) -> usize {
+        self.pos.col_nr + 1
+    }
+
+    pub fn into_error(self) -> Error {
+        self.error
+    }
+}
+
 impl<'a> fmt::Display for ErrorWithContext<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let color_red = "\u{1B}[31m";
@@ -81,14 +131,16 @@ impl<'a> fmt::Display for ErrorWithContext<'a> {
         write!(
             f,
             "parse error at line {}, column {}: #{:?}\n\n",
-            self.pos.line_nr, self.pos.col_nr, self.error,
-        );
+            self.line(),
+            self.column(),
+            self.error,
+        )?;
 
         if let Some(line) = self.line0 {
-            write!(f, "{}\n", line);
+            writeln!(f, "{}", line)?;
         }
 
-        write!(f, "{}\n", self.line1.unwrap_or(""));
+        writeln!(f, "{}", self.line1.unwrap_or(""))?;
 
         write!(
             f,
@@ -96,7 +148,7 @@ impl<'a> fmt::Display for ErrorWithContext<'a> {
             color_red,
             "↑",
             color_reset,
-            width = self.pos.col_nr
+            width = self.column()
         )
     }
 }
@@ -127,11 +179,19 @@ impl ParserContent {
         self.chars.get(self.pos.idx + 1).cloned()
     }
 
-    /// Get the current character, and move on to the next.
-    fn consume(&mut self) -> Result<char, Error> {
-        let c = self.peek();
+    /// Get the current character, and move on to the next, if it matches the
+    /// predicate. Otherwise, return the given error, leaving the position at
+    /// the offending character.
+    fn consume_if<P>(&mut self, predicate: P, error: Error) -> Result<char, Error>
+    where
+        P: FnOnce(char) -> bool,
+    {
+        let c = self.peek().ok_or(Error::UnexpectedEOF)?;
+        if !predicate(c) {
+            return Err(error);
+        }
         self.advance();
-        c.ok_or(Error::UnexpectedEOF)
+        Ok(c)
     }
 
     fn try_consume_char(&mut self, expected_c: char) -> bool {
@@ -150,30 +210,182 @@ impl ParserContent {
     }
 }
 
+/// The attribute that marks a block as raw when `ParserOptions::raw_blocks`
+/// is set: its content, including any whitespace beyond the block’s
+/// indentation, is taken literally.
+pub const RAW_ATTRIBUTE: &str = "raw";
+
+/// Whether the attributes mark a block as raw, either with the bare flag, as in
+/// `#listing[raw]`, or with `raw=true`. Other values, e.g. `raw=no`, do not.
+pub(crate) fn is_raw(attributes: &HashMap<String, String>) -> bool {
+    match attributes.get(RAW_ATTRIBUTE) {
+        Some(value) => value == RAW_ATTRIBUTE || value == "true",
+        None => false,
+    }
+}
+
+/// The input without a leading byte order mark, which is not part of the
+/// content.
+pub(crate) fn strip_bom(s: &str) -> &str {
+    s.strip_prefix('\u{FEFF}').unwrap_or(s)
+}
+
+/// The default value of `ParserOptions::max_depth`, low enough to not run out
+/// of stack space.
+pub const DEFAULT_MAX_DEPTH: usize = 256;
+
+/// Options that change what the parser accepts.
+///
+/// The limits guard against malicious input; when parsing untrusted content,
+/// set all of them.
+#[derive(Debug, Clone)]
+pub struct ParserOptions {
+    /// Allow Unicode identifiers (XID_Start followed by XID_Continue or -) as
+    /// element and attribute names. When false, only ASCII names are accepted.
+    pub unicode_names: bool,
+
+    /// Indent nested blocks with one tab per level, rather than with two
+    /// spaces per level.
+    pub tab_indentation: bool,
+
+    /// Keep comments (lines starting with #!, and inline %!{...}) in the
+    /// parsed document as `Node::Comment`, rather than dropping them.
+    pub keep_comments: bool,
+
+    /// Take the content of blocks marked with `RAW_ATTRIBUTE`, e.g.
+    /// `#listing[raw]`, literally. Off by default, so that `raw` is an
+    /// ordinary attribute unless raw blocks are asked for.
+    pub raw_blocks: bool,
+
+    /// The maximum nesting depth of block and inline elements, or None for no
+    /// limit. Defaults to `DEFAULT_MAX_DEPTH`.
+    pub max_depth: Option<usize>,
+
+    /// The maximum length of the input, in characters, or None for no limit.
+    /// A byte order mark and the CR of CRLF line endings count as well.
+    pub max_input_length: Option<usize>,
+
+    /// The maximum number of attributes on a single element, or None for no
+    /// limit.
+    pub max_attributes: Option<usize>,
+
+    /// The maximum number of nodes in the document, or None for no limit.
+    pub max_nodes: Option<usize>,
+}
+
+impl Default for ParserOptions {
+    fn default() -> Self {
+        ParserOptions {
+            unicode_names: false,
+            tab_indentation: false,
+            keep_comments: false,
+            raw_blocks: false,
+            max_depth: Some(DEFAULT_MAX_DEPTH),
+            max_input_length: None,
+            max_attributes: None,
+            max_nodes: None,
+        }
+    }
+}
+
+impl ParserOptions {
+    /// Whether a block with these attributes is read as a raw block.
+    pub(crate) fn is_raw_block(&self, attributes: &HashMap<String, String>) -> bool {
+        self.raw_blocks && is_raw(attributes)
+    }
+
+    pub(crate) fn is_name_head_char(&self, c: char) -> bool {
+        if self.unicode_names {
+            UnicodeXID::is_xid_start(c)
+        } else {
+            c.is_ascii_alphabetic()
+        }
+    }
+
+    pub(crate) fn is_name_tail_char(&self, c: char) -> bool {
+        if self.unicode_names {
+            c == '-' || UnicodeXID::is_xid_continue(c)
+        } else {
+            matches!(c, 'a'..='z' | 'A'..='Z' | '-' | '_' | '0'..='9')
+        }
+    }
+
+    /// The character used for one unit of indentation.
+    pub(crate) fn indentation_char(&self) -> char {
+        if self.tab_indentation {
+            '\t'
+        } else {
+            ' '
+        }
+    }
+
+    /// The number of indentation characters that make up one level.
+    pub(crate) fn indentation_width(&self) -> usize {
+        if self.tab_indentation {
+            1
+        } else {
+            2
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Parser {
     content: ParserContent,
+    options: ParserOptions,
+    input_too_long: bool,
+    depth: usize,
+    node_count: usize,
 }
 
 impl Parser {
     pub fn new(s: &str) -> Self {
+        Parser::with_options(s, ParserOptions::default())
+    }
+
+    pub fn with_options(s: &str, options: ParserOptions) -> Self {
+        // Check the length before splitting the input into characters, so that
+        // the limit also bounds memory use.
+        let input_too_long = options
+            .max_input_length
+            .is_some_and(|limit| s.chars().nth(limit).is_some());
+        let chars = if input_too_long {
+            vec![]
+        } else {
+            Parser::normalize(s)
+        };
+
         Parser {
             content: ParserContent {
-                chars: s.chars().collect(),
+                chars,
                 pos: Pos::new(),
             },
+            options,
+            input_too_long,
+            depth: 0,
+            node_count: 0,
         }
     }
 
-    pub fn call(s: &str) -> Result<Vec<Node>, ErrorWithContext> {
-        let mut parser = Parser::new(s);
+    pub fn call(s: &str) -> Result<Vec<Node>, ErrorWithContext<'_>> {
+        Parser::call_with_options(s, ParserOptions::default())
+    }
+
+    pub fn call_with_options(
+        s: &str,
+        options: ParserOptions,
+    ) -> Result<Vec<Node>, ErrorWithContext<'_>> {
+        let mut parser = Parser::with_options(s, options);
         let res = parser.run();
         match res {
             Ok(parsed) => Ok(parsed),
             Err(error) => {
-                let mut lines = s.lines();
-                let mut line0;
-                let mut line1;
+                // Take the context from the input as the parser saw it, so
+                // that a byte order mark does not shift the arrow. `lines`
+                // already drops the CR of CRLF line endings.
+                let mut lines = strip_bom(s).lines();
+                let line0;
+                let line1;
                 if parser.content.pos.line_nr > 0 {
                     let mut lines = lines.skip(parser.content.pos.line_nr - 1);
                     line0 = lines.next();
@@ -184,17 +396,21 @@ impl Parser {
                 }
 
                 Err(ErrorWithContext {
-                    error: error,
+                    error,
                     pos: parser.content.pos,
-                    line0: line0,
-                    line1: line1,
+                    line0,
+                    line1,
                 })
             }
         }
     }
 
     pub fn run(&mut self) -> Result<Vec<Node>, Error> {
-        // Skip blank lines
+        if self.input_too_long {
+            return Err(Error::InputTooLong);
+        }
+
+        let mut nodes = vec![];
         loop {
             if self.content.is_eof() {
                 break;
@@ -207,68 +423,88 @@ impl Parser {
                     self.content.pos.line_nr += 1;
                     self.content.pos.col_nr = 0;
                 }
-                None => break,
+                None if self.is_comment_line() => {
+                    if let Some(comment) = self.read_comment_line()? {
+                        nodes.push(comment);
+                    }
+                }
+                None => nodes.push(self.read_block_with_children(0)?),
             }
         }
 
-        let mut nodes = vec![];
-        loop {
-            if self.content.is_eof() {
-                break;
-            }
+        Ok(nodes)
+    }
+
+    // Utility functions
+
+    /// Split the input into characters, dropping a leading byte order mark and
+    /// turning CRLF line endings into LF.
+    fn normalize(s: &str) -> Vec<char> {
+        let mut res = Vec::with_capacity(s.len());
+        let mut chars = strip_bom(s).chars().peekable();
 
-            nodes.push(self.read_block_with_children(0)?);
+        while let Some(c) = chars.next() {
+            if c == '\r' && chars.peek() == Some(&'\n') {
+                continue;
+            }
+            res.push(c);
         }
 
-        Ok(nodes)
+        res
     }
 
-    // Utility functions
+    // Utility functions – limits
 
-    fn is_name_head_char(c: &char) -> bool {
-        match c {
-            'a'...'z' | 'A'...'Z' => true,
-            _ => false,
+    fn exceeds(value: usize, limit: Option<usize>) -> bool {
+        limit.is_some_and(|limit| value > limit)
+    }
+
+    fn count_node(&mut self) -> Result<(), Error> {
+        self.node_count += 1;
+        if Parser::exceeds(self.node_count, self.options.max_nodes) {
+            return Err(Error::TooManyNodes);
         }
+        Ok(())
     }
 
-    fn is_name_tail_char(c: &char) -> bool {
-        match c {
-            'a'...'z' | 'A'...'Z' | '-' | '_' | '0'...'9' => true,
-            _ => false,
+    fn enter_element(&mut self) -> Result<(), Error> {
+        self.depth += 1;
+        if Parser::exceeds(self.depth, self.options.max_depth) {
+            return Err(Error::DepthLimitExceeded);
         }
+        self.count_node()
+    }
+
+    fn leave_element(&mut self) {
+        self.depth -= 1;
     }
 
     // Utility functions – reading
 
     fn read_name_head(&mut self) -> Result<char, Error> {
+        let options = &self.options;
         self.content
-            .consume()
-            .filter(Parser::is_name_head_char, Error::InvalidCharInName)
+            .consume_if(|c| options.is_name_head_char(c), Error::InvalidCharInName)
     }
 
     fn read_left_brace(&mut self) -> Result<char, Error> {
-        self.content
-            .consume()
-            .filter(|c| *c == '{', Error::ExpectedLeftBrace)
+        self.content.consume_if(|c| c == '{', Error::ExpectedLeftBrace)
     }
 
     fn read_right_brace(&mut self) -> Result<char, Error> {
-        self.content
-            .consume()
-            .filter(|c| *c == '}', Error::ExpectedRightBrace)
+        self.content.consume_if(|c| c == '}', Error::ExpectedRightBrace)
     }
 
     fn read_hash(&mut self) -> Result<char, Error> {
-        self.content
-            .consume()
-            .filter(|c| *c == '#', Error::ExpectedHash)
+        self.content.consume_if(|c| c == '#', Error::ExpectedHash)
     }
 
     fn read_space(&mut self) -> Result<char, Error> {
-        self.content
-            .consume()
-            .filter(|c| *c == ' ', Error::ExpectedSpace)
+        self.content.consume_if(|c| c == ' ', Error::ExpectedSpace)
+    }
+
+    fn read_tab(&mut self) -> Result<char, Error> {
+        self.content.consume_if(|c| c == '\t', Error::ExpectedTab)
     }
 
     // Reading -- nodes
@@ -279,10 +515,20 @@ impl Parser {
         let attributes = self.read_attributes()?;
         let mut children = vec![];
 
-        match self.content.consume() {
-            Err(_) => (),
-            Ok('\n') => {}
-            Ok(' ') => {
+        match self.content.peek() {
+            None => (),
+            Some('\n') => self.content.advance(),
+            Some(' ') if self.options.is_raw_block(&attributes) => {
+                self.content.advance();
+                let line = self.read_raw_line();
+                if !line.is_empty() {
+                    children.push(Node::String(StringNode {
+                        content: line.into(),
+                    }));
+                }
+            }
+            Some(' ') => {
+                self.content.advance();
                 let nodes = self.read_inline_nodes()?;
                 self.read_end_of_inline_content()?;
                 children.extend(nodes);
@@ -292,22 +538,26 @@ impl Parser {
 
         Ok(ElementNode {
             name: name.into(),
-            attributes: attributes,
-            children: children,
+            attributes,
+            children,
+            block: true,
         })
     }
 
     fn read_inline_element_node(&mut self) -> Result<Node, Error> {
+        self.enter_element()?;
         let name = self.read_name()?;
         let attributes = self.read_attributes()?;
         self.read_left_brace()?;
         let content = self.read_inline_nodes()?;
         self.read_right_brace()?;
+        self.leave_element();
 
         Ok(Node::Element(ElementNode {
             name: name.into(),
-            attributes: attributes,
+            attributes,
             children: content,
+            block: false,
         }))
     }
 
@@ -326,6 +576,7 @@ impl Parser {
             }
         }
 
+        self.count_node()?;
         Ok(Node::String(StringNode {
             content: res.into(),
         }))
@@ -333,9 +584,43 @@ impl Parser {
 
     // Reading -- misc
 
+    fn read_newline_node(&mut self) -> Result<Node, Error> {
+        self.count_node()?;
+        Ok(Node::String(StringNode {
+            content: "\n".into(),
+        }))
+    }
+
+    /// Read whitespace beyond the indentation of a content line. It is
+    /// significant, and kept as a string node of its own, even when followed by
+    /// something that looks like a block start.
+    fn read_extra_indentation_node(&mut self) -> Result<Option<Node>, Error> {
+        let mut res = String::new();
+        while let Some(c @ ' ') | Some(c @ '\t') = self.content.peek() {
+            self.content.advance();
+            res.push(c);
+        }
+
+        if res.is_empty() {
+            return Ok(None);
+        }
+
+        self.count_node()?;
+        Ok(Some(Node::String(StringNode {
+            content: res.into(),
+        })))
+    }
+
     fn read_block_with_children(&mut self, indent: usize) -> Result<Node, Error> {
+        self.enter_element()?;
         let mut res = self.read_block_element_node()?;
 
+        if self.options.is_raw_block(&res.attributes) {
+            self.read_raw_content(indent + 1, &mut res)?;
+            self.leave_element();
+            return Ok(Node::Element(res));
+        }
+
         let mut pending_blanks = 0;
         while !self.content.is_eof() {
             let blank_idx = self.try_read_blank_line();
@@ -346,8 +631,17 @@ impl Parser {
                     self.content.pos.col_nr = 0;
                     pending_blanks += 1;
                 }
+                None if self.is_comment_line() => {
+                    if self.detect_indentation(indent + 1)? < indent + 1 {
+                        break;
+                    }
+
+                    if let Some(comment) = self.read_comment_line()? {
+                        res.children.push(comment);
+                    }
+                }
                 None => {
-                    let sub_indentation = self.detect_indentation();
+                    let sub_indentation = self.detect_indentation(indent + 1)?;
                     if sub_indentation < indent + 1 {
                         break;
                     }
@@ -358,18 +652,17 @@ impl Parser {
                             .push(self.read_block_with_children(indent + 1)?)
                     } else {
                         if !res.children.is_empty() {
-                            res.children.push(Node::String(StringNode {
-                                content: "\n".into(),
-                            }))
+                            res.children.push(self.read_newline_node()?);
                         }
 
                         for _ in 0..pending_blanks {
-                            res.children.push(Node::String(StringNode {
-                                content: "\n".into(),
-                            }));
+                            res.children.push(self.read_newline_node()?);
                         }
 
                         pending_blanks = 0;
+                        if let Some(node) = self.read_extra_indentation_node()? {
+                            res.children.push(node);
+                        }
                         res.children.extend(self.read_inline_nodes()?);
                         self.read_end_of_inline_content()?;
                     }
@@ -377,15 +670,78 @@ impl Parser {
             }
         }
 
+        self.leave_element();
         Ok(Node::Element(res))
     }
 
+    /// Read the indented content of a raw block, and merge it with the raw
+    /// content already read on the line of the block itself.
+    fn read_raw_content(&mut self, indent: usize, res: &mut ElementNode) -> Result<(), Error> {
+        let mut lines = vec![];
+        if let Some(Node::String(node)) = res.children.pop() {
+            lines.push(node.content.into_owned());
+        }
+
+        let prefix: String = (0..indent * self.options.indentation_width())
+            .map(|_| self.options.indentation_char())
+            .collect();
+
+        let mut pending_blanks = vec![];
+        while !self.content.is_eof() {
+            let blank_idx = self.try_read_blank_line();
+            match blank_idx {
+                Some(idx) => {
+                    let end = (idx - 1).min(self.content.chars.len());
+                    let line: String = self.content.chars[self.content.pos.idx..end]
+                        .iter()
+                        .collect();
+                    pending_blanks.push(line.strip_prefix(&prefix).unwrap_or("").to_string());
+
+                    self.content.pos.idx = idx;
+                    self.content.pos.line_nr += 1;
+                    self.content.pos.col_nr = 0;
+                }
+                None => {
+                    if self.detect_indentation(indent)? < indent {
+                        break;
+                    }
+
+                    self.read_indentation(indent)?;
+                    lines.append(&mut pending_blanks);
+                    lines.push(self.read_raw_line());
+                }
+            }
+        }
+
+        if !lines.is_empty() {
+            self.count_node()?;
+            res.children.push(Node::String(StringNode {
+                content: lines.join("\n").into(),
+            }));
+        }
+
+        Ok(())
+    }
+
+    /// Read the rest of the current line literally, including its line break.
+    fn read_raw_line(&mut self) -> String {
+        let mut res = String::new();
+        while let Some(c) = self.content.peek() {
+            self.content.advance();
+            if c == '\n' {
+                break;
+            }
+            res.push(c);
+        }
+        res
+    }
+
     fn try_read_blank_line(&self) -> Option<usize> {
         let mut idx = self.content.pos.idx;
 
         loop {
             match self.content.chars.get(idx) {
-                Some(' ') => idx += 1,
+                Some(' ') | Some('\t') => idx += 1,
                 None => break Some(idx + 1),
                 Some('\n') => break Some(idx + 1),
                 _ => break None,
@@ -393,47 +749,170 @@ impl Parser {
         }
     }
 
+    /// Whether the current line, after indentation, starts with #!.
+    fn is_comment_line(&self) -> bool {
+        let mut idx = self.content.pos.idx;
+
+        while let Some(' ') | Some('\t') = self.content.chars.get(idx) {
+            idx += 1;
+        }
+
+        self.content.chars.get(idx) == Some(&'#') && self.content.chars.get(idx + 1) == Some(&'!')
+    }
+
+    /// Read a comment line, including its line break. Returns None if
+    /// comments are not kept.
+    fn read_comment_line(&mut self) -> Result<Option<Node>, Error> {
+        while let Some(' ') | Some('\t') = self.content.peek() {
+            self.content.advance();
+        }
+        self.content.advance();
+        self.content.advance();
+
+        let content = self.read_raw_line();
+        self.comment_node(content)
+    }
+
+    /// Read an inline comment, %!{...}, where the leading % has already been
+    /// consumed. Returns None if comments are not kept.
+    fn read_inline_comment(&mut self) -> Result<Option<Node>, Error> {
+        // Skip the !
+        self.content.advance();
+        self.read_left_brace()?;
+
+        let mut res = String::new();
+        loop {
+            let c = self.content.peek().ok_or(Error::UnexpectedEOF)?;
+            match c {
+                '}' => break,
+                '\n' => return Err(Error::ExpectedRightBrace),
+                '%' => {
+                    self.content.advance();
+                    let c2 = self.content.peek().ok_or(Error::UnexpectedEOF)?;
+                    match c2 {
+                        '%' | '}' => {
+                            self.content.advance();
+                            res.push(c2);
+                        }
+                        _ => return Err(Error::UnexpectedEscapeSequence),
+                    }
+                }
+                _ => {
+                    self.content.advance();
+                    res.push(c);
+                }
+            }
+        }
+
+        self.read_right_brace()?;
+        self.comment_node(res)
+    }
+
+    fn comment_node(&mut self, content: String) -> Result<Option<Node>, Error> {
+        if !self.options.keep_comments {
+            return Ok(None);
+        }
+
+        self.count_node()?;
+        Ok(Some(Node::Comment(CommentNode {
+            content: content.into(),
+        })))
+    }
+
     fn read_indentation(&mut self, indent: usize) -> Result<(), Error> {
         for _ in 0..indent {
-            self.read_space()?;
-            self.read_space()?;
+            if self.options.tab_indentation {
+                self.read_tab()?;
+            } else {
+                self.read_space()?;
+                self.read_space()?;
+            }
         }
 
         Ok(())
     }
 
-    fn detect_indentation(&self) -> usize {
+    /// Detect the indentation level of the current line, without consuming
+    /// anything. At most `max_indent` levels are inspected; whitespace beyond
+    /// that is content rather than indentation. Other whitespace after a whole
+    /// number of levels ends the indentation, so it is only an error at the
+    /// start of the line or within a level.
+    ///
+    /// A partial level is content as well, unless there is no whole level
+    /// before it, or it is followed by a block, which would be nested at no
+    /// level at all.
+    fn detect_indentation(&mut self, max_indent: usize) -> Result<usize, Error> {
+        let indentation_char = self.options.indentation_char();
+        let width = self.options.indentation_width();
+
         let mut indentation_chars = 0;
         let mut idx = self.content.pos.idx;
 
-        loop {
+        let error = loop {
+            if indentation_chars == max_indent * width {
+                break None;
+            }
+
             match self.content.chars.get(idx) {
-                Some(' ') => {
+                Some(&c) if c == indentation_char => {
                     idx += 1;
                     indentation_chars += 1;
                 }
-                _ => break,
+                Some(' ') | Some('\t')
+                    if indentation_chars == 0 || indentation_chars % width != 0 =>
+                {
+                    break Some(Error::MixedTabsAndSpaces);
+                }
+                _ => break None,
+            }
+        };
+
+        let partial = indentation_chars % width != 0;
+        let error = error.or_else(|| {
+            if partial && (indentation_chars < width || self.is_block_start_at(idx)) {
+                // Point at the last indentation character
+                idx -= 1;
+                Some(Error::OddIndentation)
+            } else {
+                None
+            }
+        });
+
+        match error {
+            Some(error) => {
+                // Move to the offending character
+                while self.content.pos.idx < idx {
+                    self.content.advance();
+                }
+                Err(error)
             }
+            None => Ok(indentation_chars / width),
         }
-
-        indentation_chars / 2
     }
 
     fn try_read_block_start(&self) -> bool {
-        match self.content.peek() {
-            Some('#') => match self.content.peek2() {
-                Some(c) if Parser::is_name_head_char(&c) => true,
-                _ => false,
+        self.is_block_start_at(self.content.pos.idx)
+    }
+
+    fn is_block_start_at(&self, idx: usize) -> bool {
+        match self.content.chars.get(idx) {
+            Some('#') => match self.content.chars.get(idx + 1) {
+                Some(&c) => self.options.is_name_head_char(c),
+                None => false,
             },
             _ => false,
         }
     }
 
     fn read_end_of_inline_content(&mut self) -> Result<(), Error> {
-        match self.content.consume() {
-            Err(_) | Ok('\n') => Ok(()),
-            Ok('}') => Err(Error::UnexpectedRightBrace),
-            _ => panic!("internal error: unexpected content after inline content"),
+        match self.content.peek() {
+            None => Ok(()),
+            Some('\n') => {
+                self.content.advance();
+                Ok(())
+            }
+            Some('}') => Err(Error::UnexpectedRightBrace),
+            _ => Err(Error::UnexpectedContentAfterInlineContent),
         }
     }
 
@@ -444,6 +923,12 @@ impl Parser {
             match c {
                 '\n' => break,
                 '}' => break,
+                '%' if self.content.peek2() == Some('!') => {
+                    self.content.advance();
+                    if let Some(comment) = self.read_inline_comment()? {
+                        res.push(comment);
+                    }
+                }
                 '%' => res.push(self.read_percent_body()?),
                 _ => res.push(self.read_string_node()?),
             }
@@ -466,13 +951,14 @@ impl Parser {
     fn read_escaped_char(&mut self) -> Result<Node, Error> {
         let c = self.content.peek().ok_or(Error::UnexpectedEOF)?;
         self.content.advance();
+        self.count_node()?;
         Ok(Node::String(StringNode {
             content: c.to_string().into(),
         }))
     }
 
     fn read_name_tail_char(&mut self) -> Option<char> {
-        let c = self.content.peek().filter(Parser::is_name_tail_char);
+        let c = self.content.peek().filter(|c| self.options.is_name_tail_char(*c));
         if c.is_some() {
             self.content.advance();
         }
@@ -546,10 +1032,17 @@ impl Parser {
                 attributes.insert(key.clone(), key);
             }
 
-            match self.content.consume()? {
-                ']' => break,
-                ',' => (),
-                _ => panic!("internal error: unexpected content after attribute value"),
+            if Parser::exceeds(attributes.len(), self.options.max_attributes) {
+                return Err(Error::TooManyAttributes);
+            }
+
+            match self.content.peek().ok_or(Error::UnexpectedEOF)? {
+                ']' => {
+                    self.content.advance();
+                    break;
+                }
+                ',' => self.content.advance(),
+                _ => return Err(Error::UnexpectedContentAfterAttribute),
             }
         }
 
@@ -559,19 +1052,21 @@ impl Parser {
 
 #[cfg(test)]
 mod tests {
-    use super::{ElementNode, Error, Node, Parser, StringNode};
+    use super::{CommentNode, ElementNode, Error, Node, Parser, ParserOptions, StringNode};
+    use DmarkTranslator;
     use std::collections::HashMap;
 
     #[test]
     fn parse_inline_string() {
         assert_eq!(
-            Parser::new(&"#p hai").run().unwrap(),
+            Parser::new("#p hai").run().unwrap(),
             vec![Node::Element(ElementNode {
                 name: "p".into(),
                 attributes: HashMap::new(),
                 children: vec![Node::String(StringNode {
                     content: "hai".into()
-                })]
+                })],
+                block: true,
             })]
         );
     }
@@ -579,11 +1074,12 @@ mod tests {
     #[test]
     fn parse_inline_string_empty() {
         assert_eq!(
-            Parser::new(&"#p ").run().unwrap(),
+            Parser::new("#p ").run().unwrap(),
             vec![Node::Element(ElementNode {
                 name: "p".into(),
                 attributes: HashMap::new(),
-                children: vec![]
+                children: vec![],
+                block: true,
             })]
         );
     }
@@ -591,15 +1087,17 @@ mod tests {
     #[test]
     fn parse_inline_element_empty() {
         assert_eq!(
-            Parser::new(&"#p %foo{}").run().unwrap(),
+            Parser::new("#p %foo{}").run().unwrap(),
             vec![Node::Element(ElementNode {
                 name: "p".into(),
                 attributes: HashMap::new(),
                 children: vec![Node::Element(ElementNode {
                     name: "foo".into(),
                     attributes: HashMap::new(),
-                    children: vec![]
-                })]
+                    children: vec![],
+                    block: false,
+                })],
+                block: true,
             })]
         );
     }
@@ -607,7 +1105,7 @@ mod tests {
     #[test]
     fn parse_inline_element_str() {
         assert_eq!(
-            Parser::new(&"#p %foo{abc}").run().unwrap(),
+            Parser::new("#p %foo{abc}").run().unwrap(),
             vec![Node::Element(ElementNode {
                 name: "p".into(),
                 attributes: HashMap::new(),
@@ -616,8 +1114,10 @@ mod tests {
                     attributes: HashMap::new(),
                     children: vec![Node::String(StringNode {
                         content: "abc".into()
-                    })]
-                })]
+                    })],
+                    block: false,
+                })],
+                block: true,
             })]
         );
     }
@@ -625,7 +1125,7 @@ mod tests {
     #[test]
     fn parse_inline_element_wrapped() {
         assert_eq!(
-            Parser::new(&"#p alpha %foo{abc} omega").run().unwrap(),
+            Parser::new("#p alpha %foo{abc} omega").run().unwrap(),
             vec![Node::Element(ElementNode {
                 name: "p".into(),
                 attributes: HashMap::new(),
@@ -638,12 +1138,14 @@ mod tests {
                         attributes: HashMap::new(),
                         children: vec![Node::String(StringNode {
                             content: "abc".into()
-                        })]
+                        })],
+                        block: false,
                     }),
                     Node::String(StringNode {
                         content: " omega".into()
                     }),
-                ]
+                ],
+                block: true,
             })]
         );
     }
@@ -651,7 +1153,7 @@ mod tests {
     #[test]
     fn parse_inline_element_nested() {
         assert_eq!(
-            Parser::new(&"#p %foo{%bar{}}").run().unwrap(),
+            Parser::new("#p %foo{%bar{}}").run().unwrap(),
             vec![Node::Element(ElementNode {
                 name: "p".into(),
                 attributes: HashMap::new(),
@@ -661,9 +1163,12 @@ mod tests {
                     children: vec![Node::Element(ElementNode {
                         name: "bar".into(),
                         attributes: HashMap::new(),
-                        children: vec![]
-                    })]
-                })]
+                        children: vec![],
+                        block: false,
+                    })],
+                    block: false,
+                })],
+                block: true,
             })]
         );
     }
@@ -671,7 +1176,7 @@ mod tests {
     #[test]
     fn parse_inline_element_escaped() {
         assert_eq!(
-            Parser::new(&"#p a %% b").run(),
+            Parser::new("#p a %% b").run(),
             Ok(vec![Node::Element(ElementNode {
                 name: "p".into(),
                 attributes: HashMap::new(),
@@ -685,30 +1190,31 @@ mod tests {
                     Node::String(StringNode {
                         content: " b".into()
                     })
-                ]
+                ],
+                block: true,
             })])
         );
     }
 
     #[test]
     fn parse_inline_element_eof1() {
-        assert_eq!(Parser::new(&"#p a %").run(), Err(Error::UnexpectedEOF));
+        assert_eq!(Parser::new("#p a %").run(), Err(Error::UnexpectedEOF));
     }
 
     #[test]
     fn parse_inline_element_eof2() {
-        assert_eq!(Parser::new(&"#p a %a").run(), Err(Error::UnexpectedEOF));
+        assert_eq!(Parser::new("#p a %a").run(), Err(Error::UnexpectedEOF));
     }
 
     #[test]
     fn parse_inline_element_eof3() {
-        assert_eq!(Parser::new(&"#p a %a{").run(), Err(Error::UnexpectedEOF));
+        assert_eq!(Parser::new("#p a %a{").run(), Err(Error::UnexpectedEOF));
     }
 
     #[test]
     fn parse_inline_element_nl1() {
         assert_eq!(
-            Parser::new(&"#p a %\nb{}").run(),
+            Parser::new("#p a %\nb{}").run(),
             Err(Error::InvalidCharInName)
         );
     }
@@ -716,7 +1222,7 @@ mod tests {
     #[test]
     fn parse_inline_element_nl2() {
         assert_eq!(
-            Parser::new(&"#p a %a\nb{}").run(),
+            Parser::new("#p a %a\nb{}").run(),
             Err(Error::ExpectedLeftBrace)
         );
     }
@@ -724,7 +1230,7 @@ mod tests {
     #[test]
     fn parse_inline_element_nl3() {
         assert_eq!(
-            Parser::new(&"#p a %a{\nb}").run(),
+            Parser::new("#p a %a{\nb}").run(),
             Err(Error::ExpectedRightBrace)
         );
     }
@@ -732,7 +1238,7 @@ mod tests {
     #[test]
     fn parse_inline_element_nl4() {
         assert_eq!(
-            Parser::new(&"#p a %a{b\n}").run(),
+            Parser::new("#p a %a{b\n}").run(),
             Err(Error::ExpectedRightBrace)
         );
     }
@@ -740,7 +1246,7 @@ mod tests {
     #[test]
     fn parse_inline_attr_empty() {
         assert_eq!(
-            Parser::new(&"#p foo %aaa[]{stuff} bar").run(),
+            Parser::new("#p foo %aaa[]{stuff} bar").run(),
             Ok(vec![Node::Element(ElementNode {
                 name: "p".into(),
                 attributes: HashMap::new(),
@@ -754,11 +1260,13 @@ mod tests {
                         children: vec![Node::String(StringNode {
                             content: "stuff".into()
                         })],
+                        block: false,
                     }),
                     Node::String(StringNode {
                         content: " bar".into()
                     })
-                ]
+                ],
+                block: true,
             })])
         );
     }
@@ -769,7 +1277,7 @@ mod tests {
         attributes.insert("x".to_string(), "1".to_string());
 
         assert_eq!(
-            Parser::new(&"#p foo %aaa[x=1]{stuff} bar").run(),
+            Parser::new("#p foo %aaa[x=1]{stuff} bar").run(),
             Ok(vec![Node::Element(ElementNode {
                 name: "p".into(),
                 attributes: HashMap::new(),
@@ -779,15 +1287,17 @@ mod tests {
                     }),
                     Node::Element(ElementNode {
                         name: "aaa".into(),
-                        attributes: attributes,
+                        attributes,
                         children: vec![Node::String(StringNode {
                             content: "stuff".into()
                         })],
+                        block: false,
                     }),
                     Node::String(StringNode {
                         content: " bar".into()
                     })
-                ]
+                ],
+                block: true,
             })])
         );
     }
@@ -798,7 +1308,7 @@ mod tests {
         attributes.insert("static".to_string(), "static".to_string());
 
         assert_eq!(
-            Parser::new(&"#p foo %aaa[static]{stuff} bar").run(),
+            Parser::new("#p foo %aaa[static]{stuff} bar").run(),
             Ok(vec![Node::Element(ElementNode {
                 name: "p".into(),
                 attributes: HashMap::new(),
@@ -808,15 +1318,17 @@ mod tests {
                     }),
                     Node::Element(ElementNode {
                         name: "aaa".into(),
-                        attributes: attributes,
+                        attributes,
                         children: vec![Node::String(StringNode {
                             content: "stuff".into()
                         })],
+                        block: false,
                     }),
                     Node::String(StringNode {
                         content: " bar".into()
                     }),
-                ]
+                ],
+                block: true,
             })])
         );
     }
@@ -827,7 +1339,7 @@ mod tests {
         attributes.insert("x".to_string(), "a%b".to_string());
 
         assert_eq!(
-            Parser::new(&"#p foo %aaa[x=a%%b]{stuff} bar").run(),
+            Parser::new("#p foo %aaa[x=a%%b]{stuff} bar").run(),
             Ok(vec![Node::Element(ElementNode {
                 name: "p".into(),
                 attributes: HashMap::new(),
@@ -837,15 +1349,17 @@ mod tests {
                     }),
                     Node::Element(ElementNode {
                         name: "aaa".into(),
-                        attributes: attributes,
+                        attributes,
                         children: vec![Node::String(StringNode {
                             content: "stuff".into()
                         })],
+                        block: false,
                     }),
                     Node::String(StringNode {
                         content: " bar".into()
                     }),
-                ]
+                ],
+                block: true,
             })])
         );
     }
@@ -856,7 +1370,7 @@ mod tests {
         attributes.insert("x".to_string(), "a,b".to_string());
 
         assert_eq!(
-            Parser::new(&"#p foo %aaa[x=a%,b]{stuff} bar").run(),
+            Parser::new("#p foo %aaa[x=a%,b]{stuff} bar").run(),
             Ok(vec![Node::Element(ElementNode {
                 name: "p".into(),
                 attributes: HashMap::new(),
@@ -866,15 +1380,17 @@ mod tests {
                     }),
                     Node::Element(ElementNode {
                         name: "aaa".into(),
-                        attributes: attributes,
+                        attributes,
                         children: vec![Node::String(StringNode {
                             content: "stuff".into()
                         })],
+                        block: false,
                     }),
                     Node::String(StringNode {
                         content: " bar".into()
                     }),
-                ]
+                ],
+                block: true,
             })])
         );
     }
@@ -882,7 +1398,7 @@ mod tests {
     #[test]
     fn parse_inline_attr_escape_other() {
         assert_eq!(
-            Parser::new(&"#p foo %aaa[x=a%?b]{stuff} bar").run(),
+            Parser::new("#p foo %aaa[x=a%?b]{stuff} bar").run(),
             Err(Error::UnexpectedEscapeSequence),
         );
     }
@@ -893,7 +1409,7 @@ mod tests {
         attributes.insert("x".to_string(), "a]b".to_string());
 
         assert_eq!(
-            Parser::new(&"#p foo %aaa[x=a%]b]{stuff} bar").run(),
+            Parser::new("#p foo %aaa[x=a%]b]{stuff} bar").run(),
             Ok(vec![Node::Element(ElementNode {
                 name: "p".into(),
                 attributes: HashMap::new(),
@@ -903,15 +1419,17 @@ mod tests {
                     }),
                     Node::Element(ElementNode {
                         name: "aaa".into(),
-                        attributes: attributes,
+                        attributes,
                         children: vec![Node::String(StringNode {
                             content: "stuff".into()
                         })],
+                        block: false,
                     }),
                     Node::String(StringNode {
                         content: " bar".into()
                     }),
-                ]
+                ],
+                block: true,
             })])
         )
     }
@@ -919,7 +1437,7 @@ mod tests {
     #[test]
     fn parse_inline_attr_escape_eol() {
         assert_eq!(
-            Parser::new(&"#p foo %aaa[x=a%\n]b]{stuff} bar").run(),
+            Parser::new("#p foo %aaa[x=a%\n]b]{stuff} bar").run(),
             Err(Error::UnexpectedEOL),
         );
     }
@@ -927,7 +1445,7 @@ mod tests {
     #[test]
     fn parse_inline_attr_escape_eof() {
         assert_eq!(
-            Parser::new(&"#p foo %aaa[x=a%").run(),
+            Parser::new("#p foo %aaa[x=a%").run(),
             Err(Error::UnexpectedEOF),
         );
     }
@@ -935,7 +1453,7 @@ mod tests {
     #[test]
     fn parse_inline_attr_early_eof() {
         assert_eq!(
-            Parser::new(&"#p foo %aaa[x=a").run(),
+            Parser::new("#p foo %aaa[x=a").run(),
             Err(Error::UnexpectedEOF),
         );
     }
@@ -943,24 +1461,25 @@ mod tests {
     #[test]
     fn parse_inline_attr_early_eol() {
         assert_eq!(
-            Parser::new(&"#p foo %aaa[x=a\n").run(),
+            Parser::new("#p foo %aaa[x=a\n").run(),
             Err(Error::UnexpectedEOL),
         );
     }
 
     #[test]
     fn parse_block_empty() {
-        assert_eq!(Parser::new(&"").run(), Ok(vec![]),);
+        assert_eq!(Parser::new("").run(), Ok(vec![]),);
     }
 
     #[test]
     fn parse_block_one_empty_el() {
         assert_eq!(
-            Parser::new(&"#p").run(),
+            Parser::new("#p").run(),
             Ok(vec![Node::Element(ElementNode {
                 name: "p".into(),
                 attributes: HashMap::new(),
                 children: vec![],
+                block: true,
             })]),
         );
     }
@@ -968,11 +1487,12 @@ mod tests {
     #[test]
     fn parse_block_one_empty_el_with_space() {
         assert_eq!(
-            Parser::new(&"#p ").run(),
+            Parser::new("#p ").run(),
             Ok(vec![Node::Element(ElementNode {
                 name: "p".into(),
                 attributes: HashMap::new(),
                 children: vec![],
+                block: true,
             })]),
         );
     }
@@ -980,7 +1500,7 @@ mod tests {
     #[test]
     fn parse_block_one_el_without_space() {
         assert_eq!(
-            Parser::new(&"#p%a{b}").run(),
+            Parser::new("#p%a{b}").run(),
             Err(Error::UnexpectedContentAfterBlockName),
         );
     }
@@ -988,13 +1508,14 @@ mod tests {
     #[test]
     fn parse_block_one_el_with_string() {
         assert_eq!(
-            Parser::new(&"#p hi").run(),
+            Parser::new("#p hi").run(),
             Ok(vec![Node::Element(ElementNode {
                 name: "p".into(),
                 attributes: HashMap::new(),
                 children: vec![Node::String(StringNode {
                     content: "hi".into()
                 })],
+                block: true,
             })]),
         );
     }
@@ -1002,7 +1523,7 @@ mod tests {
     #[test]
     fn parse_block_one_el_with_string_with_escaped_percent() {
         assert_eq!(
-            Parser::new(&"#p hi %%").run(),
+            Parser::new("#p hi %%").run(),
             Ok(vec![Node::Element(ElementNode {
                 name: "p".into(),
                 attributes: HashMap::new(),
@@ -1014,6 +1535,7 @@ mod tests {
                         content: "%".into()
                     })
                 ],
+                block: true,
             })]),
         );
     }
@@ -1021,7 +1543,7 @@ mod tests {
     #[test]
     fn parse_block_one_el_with_string_with_escaped_rbrace() {
         assert_eq!(
-            Parser::new(&"#p hi %}").run(),
+            Parser::new("#p hi %}").run(),
             Ok(vec![Node::Element(ElementNode {
                 name: "p".into(),
                 attributes: HashMap::new(),
@@ -1033,6 +1555,7 @@ mod tests {
                         content: "}".into()
                     })
                 ],
+                block: true,
             })]),
         );
     }
@@ -1040,13 +1563,14 @@ mod tests {
     #[test]
     fn parse_block_one_el_name_with_dash() {
         assert_eq!(
-            Parser::new(&"#intro-para hi").run(),
+            Parser::new("#intro-para hi").run(),
             Ok(vec![Node::Element(ElementNode {
                 name: "intro-para".into(),
                 attributes: HashMap::new(),
                 children: vec![Node::String(StringNode {
                     content: "hi".into()
                 })],
+                block: true,
             })]),
         );
     }
@@ -1054,13 +1578,14 @@ mod tests {
     #[test]
     fn parse_block_one_el_name_with_underscore() {
         assert_eq!(
-            Parser::new(&"#intro_para hi").run(),
+            Parser::new("#intro_para hi").run(),
             Ok(vec![Node::Element(ElementNode {
                 name: "intro_para".into(),
                 attributes: HashMap::new(),
                 children: vec![Node::String(StringNode {
                     content: "hi".into()
                 })],
+                block: true,
             })]),
         );
     }
@@ -1068,13 +1593,14 @@ mod tests {
     #[test]
     fn parse_block_one_el_name_with_uppercase() {
         assert_eq!(
-            Parser::new(&"#introPara hi").run(),
+            Parser::new("#introPara hi").run(),
             Ok(vec![Node::Element(ElementNode {
                 name: "introPara".into(),
                 attributes: HashMap::new(),
                 children: vec![Node::String(StringNode {
                     content: "hi".into()
                 })],
+                block: true,
             })]),
         );
     }
@@ -1082,13 +1608,14 @@ mod tests {
     #[test]
     fn parse_block_one_el_attr_empty() {
         assert_eq!(
-            Parser::new(&"#foo[] hi").run(),
+            Parser::new("#foo[] hi").run(),
             Ok(vec![Node::Element(ElementNode {
                 name: "foo".into(),
                 attributes: HashMap::new(),
                 children: vec![Node::String(StringNode {
                     content: "hi".into()
                 })],
+                block: true,
             })]),
         );
     }
@@ -1099,13 +1626,14 @@ mod tests {
         attributes.insert("abc".to_string(), "xyz".to_string());
 
         assert_eq!(
-            Parser::new(&"#foo[abc=xyz] hi").run(),
+            Parser::new("#foo[abc=xyz] hi").run(),
             Ok(vec![Node::Element(ElementNode {
                 name: "foo".into(),
-                attributes: attributes,
+                attributes,
                 children: vec![Node::String(StringNode {
                     content: "hi".into()
                 })],
+                block: true,
             })]),
         );
     }
@@ -1116,13 +1644,14 @@ mod tests {
         attributes.insert("intended-audience".to_string(), "learner".to_string());
 
         assert_eq!(
-            Parser::new(&"#foo[intended-audience=learner] hi").run(),
+            Parser::new("#foo[intended-audience=learner] hi").run(),
             Ok(vec![Node::Element(ElementNode {
                 name: "foo".into(),
-                attributes: attributes,
+                attributes,
                 children: vec![Node::String(StringNode {
                     content: "hi".into()
                 })],
+                block: true,
             })]),
         );
     }
@@ -1133,13 +1662,14 @@ mod tests {
         attributes.insert("intended_audience".to_string(), "learner".to_string());
 
         assert_eq!(
-            Parser::new(&"#foo[intended_audience=learner] hi").run(),
+            Parser::new("#foo[intended_audience=learner] hi").run(),
             Ok(vec![Node::Element(ElementNode {
                 name: "foo".into(),
-                attributes: attributes,
+                attributes,
                 children: vec![Node::String(StringNode {
                     content: "hi".into()
                 })],
+                block: true,
             })]),
         );
     }
@@ -1150,13 +1680,14 @@ mod tests {
         attributes.insert("intendedAudience".to_string(), "learner".to_string());
 
         assert_eq!(
-            Parser::new(&"#foo[intendedAudience=learner] hi").run(),
+            Parser::new("#foo[intendedAudience=learner] hi").run(),
             Ok(vec![Node::Element(ElementNode {
                 name: "foo".into(),
-                attributes: attributes,
+                attributes,
                 children: vec![Node::String(StringNode {
                     content: "hi".into()
                 })],
+                block: true,
             })]),
         );
     }
@@ -1167,13 +1698,14 @@ mod tests {
         attributes.insert("over-9000".to_string(), "yes".to_string());
 
         assert_eq!(
-            Parser::new(&"#foo[over-9000=yes] hi").run(),
+            Parser::new("#foo[over-9000=yes] hi").run(),
             Ok(vec![Node::Element(ElementNode {
                 name: "foo".into(),
-                attributes: attributes,
+                attributes,
                 children: vec![Node::String(StringNode {
                     content: "hi".into()
                 })],
+                block: true,
             })]),
         );
     }
@@ -1184,13 +1716,14 @@ mod tests {
         attributes.insert("foo".to_string(), "foo".to_string());
 
         assert_eq!(
-            Parser::new(&"#p[foo] hi").run(),
+            Parser::new("#p[foo] hi").run(),
             Ok(vec![Node::Element(ElementNode {
                 name: "p".into(),
-                attributes: attributes,
+                attributes,
                 children: vec![Node::String(StringNode {
                     content: "hi".into()
                 })],
+                block: true,
             })]),
         );
     }
@@ -1202,13 +1735,14 @@ mod tests {
         attributes.insert("bar".to_string(), "two".to_string());
 
         assert_eq!(
-            Parser::new(&"#p[foo=one,bar=two] hi").run(),
+            Parser::new("#p[foo=one,bar=two] hi").run(),
             Ok(vec![Node::Element(ElementNode {
                 name: "p".into(),
-                attributes: attributes,
+                attributes,
                 children: vec![Node::String(StringNode {
                     content: "hi".into()
                 })],
+                block: true,
             })]),
         );
     }
@@ -1220,13 +1754,14 @@ mod tests {
         attributes.insert("bar".to_string(), "bar".to_string());
 
         assert_eq!(
-            Parser::new(&"#p[foo,bar] hi").run(),
+            Parser::new("#p[foo,bar] hi").run(),
             Ok(vec![Node::Element(ElementNode {
                 name: "p".into(),
-                attributes: attributes,
+                attributes,
                 children: vec![Node::String(StringNode {
                     content: "hi".into()
                 })],
+                block: true,
             })]),
         );
     }
@@ -1239,13 +1774,14 @@ mod tests {
         attributes.insert("donkey".to_string(), ",".to_string());
 
         assert_eq!(
-            Parser::new(&"#p[foo=%],bar=%%,donkey=%,] hi").run(),
+            Parser::new("#p[foo=%],bar=%%,donkey=%,] hi").run(),
             Ok(vec![Node::Element(ElementNode {
                 name: "p".into(),
-                attributes: attributes,
+                attributes,
                 children: vec![Node::String(StringNode {
                     content: "hi".into()
                 })],
+                block: true,
             })]),
         );
     }
@@ -1253,7 +1789,7 @@ mod tests {
     #[test]
     fn parse_block_one_el_attr_key_starts_with_dash() {
         assert_eq!(
-            Parser::new(&"#p[-foo=abc] hi").run(),
+            Parser::new("#p[-foo=abc] hi").run(),
             Err(Error::InvalidCharInName),
         );
     }
@@ -1261,7 +1797,7 @@ mod tests {
     #[test]
     fn parse_block_one_el_attr_key_starts_with_underscore() {
         assert_eq!(
-            Parser::new(&"#p[_foo=abc] hi").run(),
+            Parser::new("#p[_foo=abc] hi").run(),
             Err(Error::InvalidCharInName),
         );
     }
@@ -1269,7 +1805,7 @@ mod tests {
     #[test]
     fn parse_block_one_el_attr_key_starts_with_num() {
         assert_eq!(
-            Parser::new(&"#p[1foo=abc] hi").run(),
+            Parser::new("#p[1foo=abc] hi").run(),
             Err(Error::InvalidCharInName),
         );
     }
@@ -1277,7 +1813,7 @@ mod tests {
     #[test]
     fn parse_block_one_el_attr_value_has_unescaped_percent() {
         assert_eq!(
-            Parser::new(&"#p %ref[url=https://github.com/?q=user%3Ananoc]{eek}").run(),
+            Parser::new("#p %ref[url=https://github.com/?q=user%3Ananoc]{eek}").run(),
             Err(Error::UnexpectedEscapeSequence),
         );
     }
@@ -1285,7 +1821,7 @@ mod tests {
     #[test]
     fn parse_block_one_el_attr_early_eof() {
         assert_eq!(
-            Parser::new(&"#p %ref[url=hello").run(),
+            Parser::new("#p %ref[url=hello").run(),
             Err(Error::UnexpectedEOF),
         );
     }
@@ -1293,31 +1829,32 @@ mod tests {
     #[test]
     fn parse_block_one_el_attr_early_eof_escape() {
         assert_eq!(
-            Parser::new(&"#p %ref[url=hello%").run(),
+            Parser::new("#p %ref[url=hello%").run(),
             Err(Error::UnexpectedEOF),
         );
     }
 
     #[test]
     fn parse_block_one_el_early_eof_escape() {
-        assert_eq!(Parser::new(&"#p %").run(), Err(Error::UnexpectedEOF),);
+        assert_eq!(Parser::new("#p %").run(), Err(Error::UnexpectedEOF),);
     }
 
     #[test]
     fn parse_block_one_el_unexpected_rbrace() {
-        assert_eq!(Parser::new(&"#p }").run(), Err(Error::UnexpectedRightBrace),);
+        assert_eq!(Parser::new("#p }").run(), Err(Error::UnexpectedRightBrace),);
     }
 
     #[test]
     fn parse_block_one_el_continued_content1() {
         assert_eq!(
-            Parser::new(&"#p\n  hi").run(),
+            Parser::new("#p\n  hi").run(),
             Ok(vec![Node::Element(ElementNode {
                 name: "p".into(),
                 attributes: HashMap::new(),
                 children: vec![Node::String(StringNode {
                     content: "hi".into()
                 })],
+                block: true,
             })]),
         );
     }
@@ -1325,7 +1862,7 @@ mod tests {
     #[test]
     fn parse_block_one_el_continued_content2() {
         assert_eq!(
-            Parser::new(&"#p\n  hi\n  ho").run(),
+            Parser::new("#p\n  hi\n  ho").run(),
             Ok(vec![Node::Element(ElementNode {
                 name: "p".into(),
                 attributes: HashMap::new(),
@@ -1340,6 +1877,7 @@ mod tests {
                         content: "ho".into()
                     })
                 ],
+                block: true,
             })]),
         );
     }
@@ -1347,7 +1885,7 @@ mod tests {
     #[test]
     fn parse_block_one_el_continued_content3() {
         assert_eq!(
-            Parser::new(&"#p hi\n  ho").run(),
+            Parser::new("#p hi\n  ho").run(),
             Ok(vec![Node::Element(ElementNode {
                 name: "p".into(),
                 attributes: HashMap::new(),
@@ -1362,6 +1900,7 @@ mod tests {
                         content: "ho".into()
                     })
                 ],
+                block: true,
             })]),
         );
     }
@@ -1369,7 +1908,7 @@ mod tests {
     #[test]
     fn parse_block_one_el_continued_content4() {
         assert_eq!(
-            Parser::new(&"#p hi\n    ho").run(),
+            Parser::new("#p hi\n    ho").run(),
             Ok(vec![Node::Element(ElementNode {
                 name: "p".into(),
                 attributes: HashMap::new(),
@@ -1381,9 +1920,13 @@ mod tests {
                         content: "\n".into()
                     }),
                     Node::String(StringNode {
-                        content: "  ho".into()
+                        content: "  ".into()
+                    }),
+                    Node::String(StringNode {
+                        content: "ho".into()
                     })
                 ],
+                block: true,
             })]),
         );
     }
@@ -1391,7 +1934,7 @@ mod tests {
     #[test]
     fn parse_block_one_el_continued_content5() {
         assert_eq!(
-            Parser::new(&"#p hi\n    ho\n  ha").run(),
+            Parser::new("#p hi\n    ho\n  ha").run(),
             Ok(vec![Node::Element(ElementNode {
                 name: "p".into(),
                 attributes: HashMap::new(),
@@ -1403,7 +1946,10 @@ mod tests {
                         content: "\n".into()
                     }),
                     Node::String(StringNode {
-                        content: "  ho".into()
+                        content: "  ".into()
+                    }),
+                    Node::String(StringNode {
+                        content: "ho".into()
                     }),
                     Node::String(StringNode {
                         content: "\n".into()
@@ -1412,6 +1958,7 @@ mod tests {
                         content: "ha".into()
                     })
                 ],
+                block: true,
             })]),
         );
     }
@@ -1419,7 +1966,7 @@ mod tests {
     #[test]
     fn parse_block_one_el_continued_content_nested() {
         assert_eq!(
-            Parser::new(&"#p hi\n  %#foo").run(),
+            Parser::new("#p hi\n  %#foo").run(),
             Ok(vec![Node::Element(ElementNode {
                 name: "p".into(),
                 attributes: HashMap::new(),
@@ -1437,6 +1984,7 @@ mod tests {
                         content: "foo".into()
                     })
                 ],
+                block: true,
             })]),
         );
     }
@@ -1444,7 +1992,7 @@ mod tests {
     #[test]
     fn parse_block_one_el_continued_content_hash_but_no_block() {
         assert_eq!(
-            Parser::new(&"#listing\n  calc_foo()\n  # => 123").run(),
+            Parser::new("#listing\n  calc_foo()\n  # => 123").run(),
             Ok(vec![Node::Element(ElementNode {
                 name: "listing".into(),
                 attributes: HashMap::new(),
@@ -1459,6 +2007,7 @@ mod tests {
                         content: "# => 123".into()
                     })
                 ],
+                block: true,
             })]),
         );
     }
@@ -1466,7 +2015,7 @@ mod tests {
     #[test]
     fn parse_block_one_el_nested1() {
         assert_eq!(
-            Parser::new(&"#p hi\n  #x a").run(),
+            Parser::new("#p hi\n  #x a").run(),
             Ok(vec![Node::Element(ElementNode {
                 name: "p".into(),
                 attributes: HashMap::new(),
@@ -1480,8 +2029,10 @@ mod tests {
                         children: vec![Node::String(StringNode {
                             content: "a".into()
                         })],
+                        block: true,
                     })
                 ],
+                block: true,
             })]),
         );
     }
@@ -1489,7 +2040,7 @@ mod tests {
     #[test]
     fn parse_block_one_el_nested2() {
         assert_eq!(
-            Parser::new(&"#p\n  hi\n  #x a").run(),
+            Parser::new("#p\n  hi\n  #x a").run(),
             Ok(vec![Node::Element(ElementNode {
                 name: "p".into(),
                 attributes: HashMap::new(),
@@ -1503,8 +2054,10 @@ mod tests {
                         children: vec![Node::String(StringNode {
                             content: "a".into()
                         })],
+                        block: true,
                     })
                 ],
+                block: true,
             })]),
         );
     }
@@ -1512,7 +2065,7 @@ mod tests {
     #[test]
     fn parse_block_one_el_garbage_at_eol() {
         assert_eq!(
-            Parser::new(&"#p hi}").run(),
+            Parser::new("#p hi}").run(),
             Err(Error::UnexpectedRightBrace),
         );
     }
@@ -1520,7 +2073,7 @@ mod tests {
     #[test]
     fn parse_block_two_els_simple() {
         assert_eq!(
-            Parser::new(&"#p hi\n#p ho").run(),
+            Parser::new("#p hi\n#p ho").run(),
             Ok(vec![
                 Node::Element(ElementNode {
                     name: "p".into(),
@@ -1528,6 +2081,7 @@ mod tests {
                     children: vec![Node::String(StringNode {
                         content: "hi".into()
                     })],
+                    block: true,
                 }),
                 Node::Element(ElementNode {
                     name: "p".into(),
@@ -1535,6 +2089,7 @@ mod tests {
                     children: vec![Node::String(StringNode {
                         content: "ho".into()
                     })],
+                    block: true,
                 })
             ]),
         );
@@ -1543,7 +2098,7 @@ mod tests {
     #[test]
     fn parse_block_two_els_continued() {
         assert_eq!(
-            Parser::new(&"#p hi\n  hi2\n#p ho\n  ho2").run(),
+            Parser::new("#p hi\n  hi2\n#p ho\n  ho2").run(),
             Ok(vec![
                 Node::Element(ElementNode {
                     name: "p".into(),
@@ -1559,6 +2114,7 @@ mod tests {
                             content: "hi2".into()
                         })
                     ],
+                    block: true,
                 }),
                 Node::Element(ElementNode {
                     name: "p".into(),
@@ -1574,11 +2130,624 @@ mod tests {
                             content: "ho2".into()
                         })
                     ],
+                    block: true,
                 })
             ]),
         );
     }
 
+    #[test]
+    fn parse_block_unicode_name_rejected_by_default() {
+        assert_eq!(
+            Parser::new("#p %zität{hi}").run(),
+            Err(Error::ExpectedLeftBrace),
+        );
+    }
+
+    #[test]
+    fn parse_block_unicode_names() {
+        let options = ParserOptions {
+            unicode_names: true,
+            ..ParserOptions::default()
+        };
+
+        let mut attrs = HashMap::new();
+        attrs.insert("größe".into(), "groß".into());
+
+        assert_eq!(
+            Parser::with_options("#引用 %zitat[größe=groß]{hi}", options).run(),
+            Ok(vec![Node::Element(ElementNode {
+                name: "引用".into(),
+                attributes: HashMap::new(),
+                children: vec![Node::Element(ElementNode {
+                    name: "zitat".into(),
+                    attributes: attrs,
+                    children: vec![Node::String(StringNode {
+                        content: "hi".into()
+                    })],
+                    block: false,
+                })],
+                block: true,
+            })])
+        );
+    }
+
+    #[test]
+    fn parse_block_unicode_name_invalid_head() {
+        let options = ParserOptions {
+            unicode_names: true,
+            ..ParserOptions::default()
+        };
+
+        assert_eq!(
+            Parser::with_options("#p %·a{hi}", options).run(),
+            Err(Error::InvalidCharInName),
+        );
+    }
+
+    #[test]
+    fn parse_block_odd_indentation() {
+        assert_eq!(
+            Parser::new("#p hi\n #p ho").run(),
+            Err(Error::OddIndentation),
+        );
+    }
+
+    #[test]
+    fn parse_block_odd_indentation_nested() {
+        assert_eq!(
+            Parser::new("#p hi\n  #p ho\n   ha").run(),
+            Ok(vec![Node::Element(ElementNode {
+                name: "p".into(),
+                attributes: HashMap::new(),
+                children: vec![
+                    Node::String(StringNode {
+                        content: "hi".into()
+                    }),
+                    Node::Element(ElementNode {
+                        name: "p".into(),
+                        attributes: HashMap::new(),
+                        children: vec![Node::String(StringNode {
+                            content: "ho".into()
+                        })],
+                        block: true,
+                    }),
+                    Node::String(StringNode {
+                        content: "\n".into()
+                    }),
+                    Node::String(StringNode {
+                        content: " ".into()
+                    }),
+                    Node::String(StringNode {
+                        content: "ha".into()
+                    }),
+                ],
+                block: true,
+            })])
+        );
+        assert_eq!(
+            Parser::new("#p hi\n  #p ho\n   #p ha").run(),
+            Err(Error::OddIndentation),
+        );
+    }
+
+    #[test]
+    fn parse_block_tab_in_space_indentation() {
+        assert_eq!(
+            Parser::new("#p hi\n\t#p ho").run(),
+            Err(Error::MixedTabsAndSpaces),
+        );
+    }
+
+    #[test]
+    fn parse_block_tab_after_space_indentation() {
+        assert_eq!(
+            Parser::new("#p\n  #q\n  \tx").run(),
+            Ok(vec![Node::Element(ElementNode {
+                name: "p".into(),
+                attributes: HashMap::new(),
+                children: vec![
+                    Node::Element(ElementNode {
+                        name: "q".into(),
+                        attributes: HashMap::new(),
+                        children: vec![],
+                        block: true,
+                    }),
+                    Node::String(StringNode {
+                        content: "\n".into()
+                    }),
+                    Node::String(StringNode {
+                        content: "\t".into()
+                    }),
+                    Node::String(StringNode {
+                        content: "x".into()
+                    }),
+                ],
+                block: true,
+            })])
+        );
+        assert_eq!(
+            Parser::new("#p\n \tx").run(),
+            Err(Error::MixedTabsAndSpaces),
+        );
+    }
+
+    #[test]
+    fn parse_block_tab_indentation() {
+        let options = ParserOptions {
+            tab_indentation: true,
+            ..ParserOptions::default()
+        };
+
+        assert_eq!(
+            Parser::with_options("#p hi\n\t#x a\n\t\t b", options).run(),
+            Ok(vec![Node::Element(ElementNode {
+                name: "p".into(),
+                attributes: HashMap::new(),
+                children: vec![
+                    Node::String(StringNode {
+                        content: "hi".into()
+                    }),
+                    Node::Element(ElementNode {
+                        name: "x".into(),
+                        attributes: HashMap::new(),
+                        children: vec![
+                            Node::String(StringNode {
+                                content: "a".into()
+                            }),
+                            Node::String(StringNode {
+                                content: "\n".into()
+                            }),
+                            Node::String(StringNode {
+                                content: " ".into()
+                            }),
+                            Node::String(StringNode {
+                                content: "b".into()
+                            }),
+                        ],
+                        block: true,
+                    }),
+                ],
+                block: true,
+            })])
+        );
+    }
+
+    #[test]
+    fn parse_block_space_in_tab_indentation() {
+        let options = ParserOptions {
+            tab_indentation: true,
+            ..ParserOptions::default()
+        };
+
+        assert_eq!(
+            Parser::with_options("#p hi\n  #p ho", options).run(),
+            Err(Error::MixedTabsAndSpaces),
+        );
+    }
+
+    #[test]
+    fn parse_block_crlf() {
+        assert_eq!(
+            Parser::new("#p hi\r\n  ho\r\n\r\n#p %em{ha}\r\n").run(),
+            Parser::new("#p hi\n  ho\n\n#p %em{ha}\n").run(),
+        );
+    }
+
+    #[test]
+    fn parse_block_lone_cr() {
+        assert_eq!(
+            Parser::new("#p a\rb").run(),
+            Ok(vec![Node::Element(ElementNode {
+                name: "p".into(),
+                attributes: HashMap::new(),
+                children: vec![Node::String(StringNode {
+                    content: "a\rb".into()
+                })],
+                block: true,
+            })])
+        );
+    }
+
+    #[test]
+    fn parse_block_bom() {
+        assert_eq!(
+            Parser::new("\u{FEFF}#p hi").run(),
+            Parser::new("#p hi").run(),
+        );
+    }
+
+    #[test]
+    fn parse_block_bom_stripped_once() {
+        let s = "\u{FEFF}\u{FEFF}#p hi";
+        assert_eq!(Parser::new(s).run(), Err(Error::ExpectedHash));
+        assert_eq!(Parser::call(s).unwrap_err().error, Error::ExpectedHash);
+    }
+
+    #[test]
+    fn parse_error_bom_context() {
+        let err = Parser::call("\u{FEFF}#p }").unwrap_err();
+        assert_eq!(err.line1, Some("#p }"));
+        assert_eq!(
+            err.to_string(),
+            "parse error at line 1, column 4: #UnexpectedRightBrace\n\n#p }\n\u{1B}[31m   ↑\u{1B}[0m"
+        );
+    }
+
+    #[test]
+    fn parse_error_crlf_position() {
+        let err = Parser::call("#p hi\r\n #p ho\r\n").unwrap_err();
+        assert_eq!(err.error, Error::OddIndentation);
+        assert_eq!(err.pos.line_nr, 1);
+        assert_eq!(err.pos.col_nr, 0);
+        assert_eq!(err.line0, Some("#p hi"));
+        assert_eq!(err.line1, Some(" #p ho"));
+    }
+
+    #[test]
+    fn parse_block_one_el_attr_garbage_after_key() {
+        assert_eq!(
+            Parser::new("#p[foo bar] hi").run(),
+            Err(Error::UnexpectedContentAfterAttribute),
+        );
+    }
+
+    #[test]
+    fn parse_error_display_mixed_indentation() {
+        let err = Parser::call("#p hi\n\t#p ho").unwrap_err();
+        assert_eq!(err.pos.col_nr, 0);
+        assert_eq!(
+            format!("{}", err),
+            "parse error at line 2, column 1: #MixedTabsAndSpaces\n\n#p hi\n\t#p ho\n\u{1B}[31m↑\u{1B}[0m"
+        );
+    }
+
+    #[test]
+    fn parse_limit_depth_default() {
+        let mut input = String::from("#p ");
+        for _ in 0..100_000 {
+            input.push_str("%a{");
+        }
+        assert_eq!(Parser::new(&input).run(), Err(Error::DepthLimitExceeded));
+    }
+
+    #[test]
+    fn parse_limit_depth() {
+        let options = ParserOptions {
+            max_depth: Some(3),
+            ..ParserOptions::default()
+        };
+        assert!(Parser::with_options("#p %a{%b{}}", options.clone()).run().is_ok());
+        assert_eq!(
+            Parser::with_options("#p %a{%b{%c{}}}", options.clone()).run(),
+            Err(Error::DepthLimitExceeded)
+        );
+        assert_eq!(
+            Parser::with_options("#p\n  #q\n    #r %a{}", options).run(),
+            Err(Error::DepthLimitExceeded)
+        );
+    }
+
+    #[test]
+    fn parse_limit_input_length() {
+        let options = ParserOptions {
+            max_input_length: Some(5),
+            ..ParserOptions::default()
+        };
+        assert!(Parser::with_options("#p hi", options.clone()).run().is_ok());
+        assert_eq!(
+            Parser::with_options("#p hi!", options.clone()).run(),
+            Err(Error::InputTooLong)
+        );
+        assert_eq!(
+            Parser::with_options("#p hi\r\n", options).run(),
+            Err(Error::InputTooLong)
+        );
+    }
+
+    #[test]
+    fn parse_limit_attributes() {
+        let options = ParserOptions {
+            max_attributes: Some(2),
+            ..ParserOptions::default()
+        };
+        assert!(Parser::with_options("#p[a,b] hi", options.clone()).run().is_ok());
+        assert_eq!(
+            Parser::with_options("#p[a,b,c] hi", options).run(),
+            Err(Error::TooManyAttributes)
+        );
+    }
+
+    #[test]
+    fn parse_limit_nodes() {
+        let options = ParserOptions {
+            max_nodes: Some(4),
+            ..ParserOptions::default()
+        };
+        assert!(Parser::with_options("#p a %b{c}", options.clone()).run().is_ok());
+        assert_eq!(
+            Parser::with_options("#p a %b{c}\n  d", options).run(),
+            Err(Error::TooManyNodes)
+        );
+    }
+
+    #[test]
+    fn parse_comments_dropped() {
+        assert_eq!(
+            Parser::new("#! intro\n#p a %!{note} b\n  #! more\n  c\n#! outro").run(),
+            Ok(vec![Node::Element(ElementNode {
+                name: "p".into(),
+                attributes: HashMap::new(),
+                children: vec![
+                    Node::String(StringNode {
+                        content: "a ".into()
+                    }),
+                    Node::String(StringNode {
+                        content: " b".into()
+                    }),
+                    Node::String(StringNode {
+                        content: "\n".into()
+                    }),
+                    Node::String(StringNode {
+                        content: "c".into()
+                    }),
+                ],
+                block: true,
+            })])
+        );
+    }
+
+    #[test]
+    fn parse_comments_indentation() {
+        let keep = ParserOptions {
+            keep_comments: true,
+            ..ParserOptions::default()
+        };
+
+        for options in [ParserOptions::default(), keep] {
+            let parse = |s| Parser::with_options(s, options.clone()).run();
+
+            assert_eq!(parse("#p\n  a\n#! note\n  b"), Err(Error::ExpectedHash));
+            assert_eq!(parse("#p\n  a\n #! note"), Err(Error::OddIndentation));
+            assert_eq!(
+                parse("#p\n  a\n    #! note\n  b"),
+                parse("#p\n  a\n  b").map(|mut nodes| {
+                    if options.keep_comments {
+                        if let Node::Element(ref mut p) = nodes[0] {
+                            p.children.insert(1, Node::Comment(CommentNode {
+                                content: " note".into(),
+                            }));
+                        }
+                    }
+                    nodes
+                }),
+            );
+            assert!(parse(" #! note\n#p a").is_ok());
+        }
+    }
+
+    #[test]
+    fn parse_comments_kept() {
+        let options = ParserOptions {
+            keep_comments: true,
+            ..ParserOptions::default()
+        };
+
+        assert_eq!(
+            Parser::with_options("#! intro\n#p a%!{n%}o%%te}\n  #! more\n#! outro", options).run(),
+            Ok(vec![
+                Node::Comment(CommentNode {
+                    content: " intro".into()
+                }),
+                Node::Element(ElementNode {
+                    name: "p".into(),
+                    attributes: HashMap::new(),
+                    children: vec![
+                        Node::String(StringNode {
+                            content: "a".into()
+                        }),
+                        Node::Comment(CommentNode {
+                            content: "n}o%te".into()
+                        }),
+                        Node::Comment(CommentNode {
+                            content: " more".into()
+                        }),
+                    ],
+                    block: true,
+                }),
+                Node::Comment(CommentNode {
+                    content: " outro".into()
+                }),
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_inline_comment_unterminated() {
+        assert_eq!(
+            Parser::new("#p a %!{note").run(),
+            Err(Error::UnexpectedEOF)
+        );
+        assert_eq!(
+            Parser::new("#p a %!{note\n}").run(),
+            Err(Error::ExpectedRightBrace)
+        );
+    }
+
+    fn raw_parser(s: &str) -> Parser {
+        Parser::with_options(
+            s,
+            ParserOptions {
+                raw_blocks: true,
+                ..ParserOptions::default()
+            },
+        )
+    }
+
+    #[test]
+    fn parse_block_raw() {
+        let mut attrs = HashMap::new();
+        attrs.insert("raw".into(), "raw".into());
+        attrs.insert("lang".into(), "rust".into());
+
+        assert_eq!(
+            raw_parser("#listing[raw,lang=rust]\n  fn main() {\n      println!(\"100%\");\n\n  }\n  #! not a comment\n  %#p}\n\n#p after").run(),
+            Ok(vec![
+                Node::Element(ElementNode {
+                    name: "listing".into(),
+                    attributes: attrs,
+                    children: vec![Node::String(StringNode {
+                        content: "fn main() {\n    println!(\"100%\");\n\n}\n#! not a comment\n%#p}".into()
+                    })],
+                    block: true,
+                }),
+                Node::Element(ElementNode {
+                    name: "p".into(),
+                    attributes: HashMap::new(),
+                    children: vec![Node::String(StringNode {
+                        content: "after".into()
+                    })],
+                    block: true,
+                }),
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_block_raw_inline() {
+        let mut attrs = HashMap::new();
+        attrs.insert("raw".into(), "raw".into());
+
+        assert_eq!(
+            raw_parser("#code[raw] a %b{ }\n      c\n    \n  d").run(),
+            Ok(vec![Node::Element(ElementNode {
+                name: "code".into(),
+                attributes: attrs,
+                children: vec![Node::String(StringNode {
+                    content: "a %b{ }\n    c\n  \nd".into()
+                })],
+                block: true,
+            })])
+        );
+    }
+
+    #[test]
+    fn parse_block_raw_nested_dedent() {
+        let mut attrs = HashMap::new();
+        attrs.insert("raw".into(), "raw".into());
+
+        assert_eq!(
+            raw_parser("#p\n  #pre[raw]\n    x\n  y").run(),
+            Ok(vec![Node::Element(ElementNode {
+                name: "p".into(),
+                attributes: HashMap::new(),
+                children: vec![
+                    Node::Element(ElementNode {
+                        name: "pre".into(),
+                        attributes: attrs,
+                        children: vec![Node::String(StringNode {
+                            content: "x".into()
+                        })],
+                        block: true,
+                    }),
+                    Node::String(StringNode {
+                        content: "\n".into()
+                    }),
+                    Node::String(StringNode {
+                        content: "y".into()
+                    }),
+                ],
+                block: true,
+            })])
+        );
+    }
+
+    #[test]
+    fn parse_block_raw_empty() {
+        let mut attrs = HashMap::new();
+        attrs.insert("raw".into(), "raw".into());
+
+        assert_eq!(
+            raw_parser("#pre[raw]\n\n").run(),
+            Ok(vec![Node::Element(ElementNode {
+                name: "pre".into(),
+                attributes: attrs,
+                children: vec![],
+                block: true,
+            })])
+        );
+    }
+
+    #[test]
+    fn parse_block_raw_values() {
+        let raw = raw_parser("#pre[raw=true]\n  %a").run().unwrap();
+        let not_raw = raw_parser("#pre[raw=no]\n  %a").run();
+
+        let translator = DmarkTranslator {
+            raw_blocks: true,
+            ..DmarkTranslator::new()
+        };
+        assert_eq!(translator.translate_document(&raw), "#pre[raw=true] %a\n");
+        assert_eq!(not_raw, Err(Error::UnexpectedEOF));
+    }
+
+    #[test]
+    fn parse_block_raw_off() {
+        let mut attrs = HashMap::new();
+        attrs.insert("raw".into(), "raw".into());
+
+        assert_eq!(
+            Parser::new("#p[raw] %em{x}").run(),
+            Ok(vec![Node::Element(ElementNode {
+                name: "p".into(),
+                attributes: attrs,
+                children: vec![Node::Element(ElementNode {
+                    name: "em".into(),
+                    attributes: HashMap::new(),
+                    children: vec![Node::String(StringNode {
+                        content: "x".into()
+                    })],
+                    block: false,
+                })],
+                block: true,
+            })])
+        );
+    }
+
+    #[test]
+    fn parse_block_over_indented_block_start() {
+        assert_eq!(
+            Parser::new("#pre\n  a\n      #b %em{c}").run(),
+            Ok(vec![Node::Element(ElementNode {
+                name: "pre".into(),
+                attributes: HashMap::new(),
+                children: vec![
+                    Node::String(StringNode {
+                        content: "a".into()
+                    }),
+                    Node::String(StringNode {
+                        content: "\n".into()
+                    }),
+                    Node::String(StringNode {
+                        content: "    ".into()
+                    }),
+                    Node::String(StringNode {
+                        content: "#b ".into()
+                    }),
+                    Node::Element(ElementNode {
+                        name: "em".into(),
+                        attributes: HashMap::new(),
+                        children: vec![Node::String(StringNode {
+                            content: "c".into()
+                        })],
+                        block: false,
+                    }),
+                ],
+                block: true,
+            })])
+        );
+    }
+
     /*
     expect(parse('#p %%')).to eq [
     expect(parse('#p %}')).to eq [
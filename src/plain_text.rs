@@ -0,0 +1,224 @@
+use super::util::{is_block, text_content};
+use super::{CommentNode, ElementNode, Node, StringNode, Translator};
+
+/// Translates D★Mark to plain text, without any markup.
+///
+/// Paragraphs are word-wrapped to `width` columns. Blocks nested in other
+/// blocks, such as the content of a `blockquote`, are indented by `indent`
+/// spaces, and list items are marked with a dash (`ul`) or numbered (`ol`).
+///
+/// The context is the width available to the node being translated.
+///
+/// # Examples
+///
+/// ```
+/// use d_mark::{Parser, PlainTextTranslator};
+///
+/// let parsed = Parser::call("#p I %em{love} Rust!").expect("parsing failed");
+/// let text = PlainTextTranslator::new(10).translate_document(&parsed);
+/// assert_eq!(text, "I love\nRust!\n");
+/// ```
+pub struct PlainTextTranslator {
+    pub width: usize,
+    pub indent: usize,
+}
+
+impl PlainTextTranslator {
+    pub fn new(width: usize) -> Self {
+        PlainTextTranslator { width, indent: 2 }
+    }
+
+    /// Translate a whole document, as returned by the parser.
+    pub fn translate_document(&self, nodes: &[Node]) -> String {
+        let mut res = self.translate_blocks(nodes, self.width);
+        if !res.is_empty() {
+            res.push('\n');
+        }
+        res
+    }
+
+    fn translate_inline(&self, nodes: &[Node], width: usize) -> String {
+        nodes.iter().map(|n| self.translate(n, width)).collect()
+    }
+
+    /// Translate nodes into blocks separated by blank lines. Runs of inline
+    /// nodes become wrapped paragraphs.
+    fn translate_blocks(&self, nodes: &[Node], width: usize) -> String {
+        let mut blocks = vec![];
+        let mut inline = String::new();
+
+        for node in nodes {
+            if is_block(node) {
+                blocks.push(wrap(&inline, width));
+                inline.clear();
+                blocks.push(self.translate(node, width));
+            } else {
+                inline.push_str(&self.translate(node, width));
+            }
+        }
+        blocks.push(wrap(&inline, width));
+
+        blocks.retain(|b| !b.is_empty());
+        blocks.join("\n\n")
+    }
+
+    fn translate_list(&self, node: &ElementNode, ordered: bool, width: usize) -> String {
+        node.children
+            .iter()
+            .filter_map(|c| match c {
+                Node::Element(n) => Some(n),
+                _ => None,
+            }).enumerate()
+            .map(|(i, item)| {
+                let marker = if ordered {
+                    format!("{}. ", i + 1)
+                } else {
+                    "- ".to_string()
+                };
+                self.translate_list_item(item, &marker, width)
+            }).collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    fn translate_list_item(&self, node: &ElementNode, marker: &str, width: usize) -> String {
+        let marker_width = marker.chars().count();
+        let content = self.translate_blocks(&node.children, width.saturating_sub(marker_width));
+
+        let mut lines = content.lines();
+        let first = lines.next().unwrap_or("");
+        let rest = indent_lines(&lines.collect::<Vec<&str>>().join("\n"), marker_width);
+        if rest.is_empty() {
+            format!("{}{}", marker, first)
+        } else {
+            format!("{}{}\n{}", marker, first, rest)
+        }
+    }
+}
+
+impl Translator<String, usize> for PlainTextTranslator {
+    fn translate_element(&self, node: &ElementNode, width: usize) -> String {
+        match node.name.as_ref() {
+            "p" => self.translate_blocks(&node.children, width),
+            "h1" | "h2" => {
+                let text = wrap(&self.translate_inline(&node.children, width), width);
+                let underline = if node.name == "h1" { "=" } else { "-" };
+                let len = text.lines().map(|l| l.chars().count()).max().unwrap_or(0);
+                format!("{}\n{}", text, underline.repeat(len))
+            }
+            "h3" | "h4" | "h5" | "h6" => wrap(&self.translate_inline(&node.children, width), width),
+            "listing" => indent_lines(
+                text_content(&node.children).trim_end_matches('\n'),
+                self.indent,
+            ),
+            "ul" => self.translate_list(node, false, width),
+            "ol" => self.translate_list(node, true, width),
+            "li" => self.translate_list_item(node, "- ", width),
+            "link" => {
+                let text = self.translate_inline(&node.children, width);
+                match node.attributes.get("target") {
+                    Some(target) if *target != text => format!("{} ({})", text, target),
+                    _ => text,
+                }
+            }
+            _ if node.name == "blockquote" || node.children.iter().any(is_block) => indent_lines(
+                &self.translate_blocks(&node.children, width.saturating_sub(self.indent)),
+                self.indent,
+            ),
+            _ if node.block => self.translate_blocks(&node.children, width),
+            _ => self.translate_inline(&node.children, width),
+        }
+    }
+
+    fn translate_string(&self, node: &StringNode, _width: usize) -> String {
+        node.content.to_string()
+    }
+
+    fn translate_comment(&self, _node: &CommentNode, _width: usize) -> String {
+        String::new()
+    }
+}
+
+/// Word-wrap the text to the given width, collapsing whitespace. Words longer
+/// than the width are put on a line of their own.
+fn wrap(text: &str, width: usize) -> String {
+    let mut res = String::new();
+    let mut line_width = 0;
+
+    for word in text.split_whitespace() {
+        let word_width = word.chars().count();
+        if line_width > 0 && line_width + 1 + word_width > width {
+            res.push('\n');
+            line_width = 0;
+        } else if line_width > 0 {
+            res.push(' ');
+            line_width += 1;
+        }
+        res.push_str(word);
+        line_width += word_width;
+    }
+
+    res
+}
+
+fn indent_lines(text: &str, indent: usize) -> String {
+    let prefix = " ".repeat(indent);
+    text.lines()
+        .map(|l| {
+            if l.is_empty() {
+                String::new()
+            } else {
+                format!("{}{}", prefix, l)
+            }
+        }).collect::<Vec<String>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PlainTextTranslator;
+    use Parser;
+
+    fn text(s: &str, width: usize) -> String {
+        PlainTextTranslator::new(width).translate_document(&Parser::call(s).unwrap())
+    }
+
+    #[test]
+    fn wrapping() {
+        assert_eq!(
+            text("#p The quick %em{brown} fox\n  jumps over the lazy dog.\n#p Supercalifragilistic yes", 12),
+            "The quick\nbrown fox\njumps over\nthe lazy\ndog.\n\nSupercalifragilistic\nyes\n"
+        );
+    }
+
+    #[test]
+    fn headings() {
+        assert_eq!(
+            text("#h1 Title\n#h2 Sub title\n#h3 Other\n#p text", 40),
+            "Title\n=====\n\nSub title\n---------\n\nOther\n\ntext\n"
+        );
+    }
+
+    #[test]
+    fn lists() {
+        assert_eq!(
+            text("#ol\n  #li one two three\n  #li\n    #p four\n    #ul\n      #li five\n#ul\n  #li six", 12),
+            "1. one two\n   three\n2. four\n\n   - five\n\n- six\n"
+        );
+    }
+
+    #[test]
+    fn nested_blocks() {
+        assert_eq!(
+            text("#p Before:\n#blockquote\n  #p Quoted text here.\n  #listing[raw]\n    x  = 1\n#p See %link[target=http://a.org]{the site}.", 14),
+            "Before:\n\n  Quoted text\n  here.\n\n    x  = 1\n\nSee the site\n(http://a.org).\n"
+        );
+    }
+
+    #[test]
+    fn unknown_blocks() {
+        assert_eq!(
+            text("#note a b c\n#note d\n#p e\n  #aside f %kbd{g}", 3),
+            "a b\nc\n\nd\n\ne\n\nf g\n"
+        );
+    }
+}
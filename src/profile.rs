@@ -0,0 +1,178 @@
+use super::{ElementNode, Node};
+
+/// Removes the elements that are meant for other profiles, e.g. to leave out
+/// `#p[only=print]` from a web page.
+///
+/// An element with an `only` attribute is kept when one of the profiles it
+/// lists is active, and an element with an `except` attribute is kept when
+/// none of them is. Profiles in these attributes are separated by whitespace.
+/// The children of a removed element are removed along with it.
+///
+/// # Examples
+///
+/// ```
+/// use d_mark::{Parser, PlainTextTranslator, ProfileFilter};
+///
+/// let parsed = Parser::call("#p[only=web] Read on.\n#p[except=web] Turn the page.")
+///     .expect("parsing failed");
+/// let filtered = ProfileFilter::new(&["print"]).filter(parsed);
+/// assert_eq!(PlainTextTranslator::new(72).translate_document(&filtered), "Turn the page.\n");
+/// ```
+#[derive(Debug, Clone)]
+pub struct ProfileFilter {
+    /// The active profiles.
+    pub profiles: Vec<String>,
+
+    /// The attribute that lists the profiles an element is only meant for.
+    pub only_attribute: String,
+
+    /// The attribute that lists the profiles an element is not meant for.
+    pub except_attribute: String,
+}
+
+impl ProfileFilter {
+    pub fn new<S: AsRef<str>>(profiles: &[S]) -> Self {
+        ProfileFilter {
+            profiles: profiles.iter().map(|p| p.as_ref().to_string()).collect(),
+            only_attribute: "only".into(),
+            except_attribute: "except".into(),
+        }
+    }
+
+    /// Whether the element is meant for the active profiles, regardless of its
+    /// ancestors.
+    pub fn includes(&self, node: &ElementNode) -> bool {
+        let is_active = |attribute: &str| {
+            node.attributes
+                .get(attribute)
+                .map(|v| v.split_whitespace().any(|p| self.profiles.iter().any(|a| a == p)))
+        };
+
+        is_active(&self.only_attribute).unwrap_or(true)
+            && !is_active(&self.except_attribute).unwrap_or(false)
+    }
+
+    /// Remove the elements that are not meant for the active profiles.
+    /// Whitespace that was only there to separate a removed element from the
+    /// text around it goes with it.
+    pub fn filter(&self, nodes: Vec<Node>) -> Vec<Node> {
+        let mut res = vec![];
+        let mut removed_first = false;
+        let mut removed_last = false;
+
+        for node in nodes {
+            match node {
+                Node::Element(n) => if self.includes(&n) {
+                    res.push(Node::Element(ElementNode {
+                        children: self.filter(n.children),
+                        ..n
+                    }));
+                    removed_last = false;
+                } else {
+                    removed_first = removed_first || res.is_empty();
+                    removed_last = true;
+                },
+                Node::String(mut n) => {
+                    let after_space = match res.last() {
+                        Some(Node::String(prev)) => prev.content.ends_with(char::is_whitespace),
+                        _ => false,
+                    };
+                    if removed_last && after_space {
+                        n.content = n.content.trim_start().to_string().into();
+                    }
+                    res.push(Node::String(n));
+                    removed_last = false;
+                }
+                other => {
+                    res.push(other);
+                    removed_last = false;
+                }
+            }
+        }
+
+        if removed_first {
+            if let Some(Node::String(first)) = res.first_mut() {
+                first.content = first.content.trim_start().to_string().into();
+            }
+        }
+        if removed_last {
+            if let Some(Node::String(last)) = res.last_mut() {
+                last.content = last.content.trim_end().to_string().into();
+            }
+        }
+        res.retain(|n| match n {
+            Node::String(s) => !s.content.is_empty(),
+            _ => true,
+        });
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ProfileFilter;
+    use {DmarkTranslator, Parser};
+
+    fn filter(filter: &ProfileFilter, s: &str) -> String {
+        let parsed = Parser::call(s).unwrap();
+        DmarkTranslator::new().translate_document(&filter.filter(parsed))
+    }
+
+    #[test]
+    fn only_and_except() {
+        let s = concat!(
+            "#p[only=web] a\n",
+            "#p[only=print ebook] b\n",
+            "#p[except=web] c %em[only=print]{d}\n",
+            "#ul\n",
+            "  #li[only=print]\n",
+            "    #p e\n",
+            "  #li f",
+        );
+
+        assert_eq!(
+            filter(&ProfileFilter::new(&["web"]), s),
+            "#p[only=web] a\n#ul\n  #li f\n"
+        );
+        assert_eq!(
+            filter(&ProfileFilter::new(&["print"]), s),
+            concat!(
+                "#p[only=print ebook] b\n",
+                "#p[except=web] c %em[only=print]{d}\n",
+                "#ul\n",
+                "  #li[only=print]\n",
+                "    #p e\n",
+                "  #li f\n",
+            )
+        );
+        assert_eq!(
+            filter(&ProfileFilter::new::<&str>(&[]), s),
+            "#p[except=web] c\n#ul\n  #li f\n"
+        );
+    }
+
+    #[test]
+    fn whitespace_around_removed_inlines() {
+        assert_eq!(
+            filter(
+                &ProfileFilter::new(&["web"]),
+                "#p %em[only=print]{a} b %em[only=print]{c} d %em[only=print]{e}"
+            ),
+            "#p b d\n"
+        );
+    }
+
+    #[test]
+    fn custom_attributes() {
+        let custom = ProfileFilter {
+            only_attribute: "if".into(),
+            except_attribute: "unless".into(),
+            ..ProfileFilter::new(&["beta"])
+        };
+
+        assert_eq!(
+            filter(&custom, "#p[if=beta] a\n#p[unless=beta] b\n#p[only=web] c"),
+            "#p[if=beta] a\n#p[only=web] c\n"
+        );
+    }
+}
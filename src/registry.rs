@@ -0,0 +1,216 @@
+use super::{CommentNode, ElementNode, StringNode, Translator};
+
+use std::collections::HashMap;
+
+/// A function that translates an element. It gets the registry, so that it
+/// can translate the children of the element.
+pub type ElementHandler<T, C> = Box<dyn Fn(&TranslatorRegistry<T, C>, &ElementNode, C) -> T>;
+
+/// A function that decides whether a handler applies to an element, e.g. by
+/// looking at its attributes.
+pub type ElementPredicate = Box<dyn Fn(&ElementNode) -> bool>;
+
+type StringHandler<T, C> = Box<dyn Fn(&StringNode, C) -> T>;
+type CommentHandler<T, C> = Box<dyn Fn(&CommentNode, C) -> T>;
+
+struct Entry<T, C> {
+    predicate: Option<ElementPredicate>,
+    handler: ElementHandler<T, C>,
+}
+
+/// A translator that dispatches on element names, to handlers that can be
+/// registered, overridden and shared.
+///
+/// Handlers registered later take precedence over earlier ones for the same
+/// element name, so that a registry can be built on top of a base one.
+/// Handlers with a predicate only apply to the elements it accepts. Elements
+/// without a handler go to the fallback handler.
+///
+/// # Examples
+///
+/// ```
+/// use d_mark::{Parser, Translator, TranslatorRegistry};
+///
+/// let mut registry = TranslatorRegistry::new(
+///     |r, node, ()| node.children().iter().map(|c| r.translate(c, ())).collect(),
+///     |node, ()| node.content().to_string(),
+///     |_, ()| String::new(),
+/// );
+/// registry.on("em", |r, node, ()| {
+///     let content: String = node.children().iter().map(|c| r.translate(c, ())).collect();
+///     format!("<em>{}</em>", content)
+/// });
+///
+/// let parsed = Parser::call("#p I %em{love} Rust!").expect("parsing failed");
+/// let html: String = parsed.iter().map(|n| registry.translate(n, ())).collect();
+/// assert_eq!(html, "I <em>love</em> Rust!");
+/// ```
+pub struct TranslatorRegistry<T, C> {
+    elements: HashMap<String, Vec<Entry<T, C>>>,
+    fallback: ElementHandler<T, C>,
+    string: StringHandler<T, C>,
+    comment: CommentHandler<T, C>,
+}
+
+impl<T, C> TranslatorRegistry<T, C> {
+    pub fn new<F, S, M>(fallback: F, string: S, comment: M) -> Self
+    where
+        F: Fn(&TranslatorRegistry<T, C>, &ElementNode, C) -> T + 'static,
+        S: Fn(&StringNode, C) -> T + 'static,
+        M: Fn(&CommentNode, C) -> T + 'static,
+    {
+        TranslatorRegistry {
+            elements: HashMap::new(),
+            fallback: Box::new(fallback),
+            string: Box::new(string),
+            comment: Box::new(comment),
+        }
+    }
+
+    /// Register a handler for elements with the given name.
+    pub fn on<F>(&mut self, name: &str, handler: F) -> &mut Self
+    where
+        F: Fn(&TranslatorRegistry<T, C>, &ElementNode, C) -> T + 'static,
+    {
+        self.push(name, None, Box::new(handler))
+    }
+
+    /// Register a handler for elements with the given name, which only applies
+    /// to elements accepted by the predicate.
+    pub fn on_if<P, F>(&mut self, name: &str, predicate: P, handler: F) -> &mut Self
+    where
+        P: Fn(&ElementNode) -> bool + 'static,
+        F: Fn(&TranslatorRegistry<T, C>, &ElementNode, C) -> T + 'static,
+    {
+        self.push(name, Some(Box::new(predicate)), Box::new(handler))
+    }
+
+    /// Replace the handler for elements that no other handler applies to.
+    pub fn on_other<F>(&mut self, handler: F) -> &mut Self
+    where
+        F: Fn(&TranslatorRegistry<T, C>, &ElementNode, C) -> T + 'static,
+    {
+        self.fallback = Box::new(handler);
+        self
+    }
+
+    /// Add the element handlers of the given registry, which take precedence
+    /// over the ones already registered. Its fallback, string and comment
+    /// handlers are not used.
+    pub fn extend(&mut self, other: TranslatorRegistry<T, C>) -> &mut Self {
+        for (name, entries) in other.elements {
+            self.elements.entry(name).or_default().extend(entries);
+        }
+        self
+    }
+
+    fn push(
+        &mut self,
+        name: &str,
+        predicate: Option<ElementPredicate>,
+        handler: ElementHandler<T, C>,
+    ) -> &mut Self {
+        self.elements
+            .entry(name.to_string())
+            .or_default()
+            .push(Entry { predicate, handler });
+        self
+    }
+
+    fn handler_for(&self, node: &ElementNode) -> &ElementHandler<T, C> {
+        self.elements
+            .get(node.name.as_ref())
+            .and_then(|entries| {
+                entries
+                    .iter()
+                    .rev()
+                    .find(|e| e.predicate.as_ref().is_none_or(|p| p(node)))
+            }).map_or(&self.fallback, |e| &e.handler)
+    }
+}
+
+impl<T, C> Translator<T, C> for TranslatorRegistry<T, C> {
+    fn translate_element(&self, node: &ElementNode, context: C) -> T {
+        (self.handler_for(node))(self, node, context)
+    }
+
+    fn translate_string(&self, node: &StringNode, context: C) -> T {
+        (self.string)(node, context)
+    }
+
+    fn translate_comment(&self, node: &CommentNode, context: C) -> T {
+        (self.comment)(node, context)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TranslatorRegistry;
+    use {ElementNode, Parser, Translator};
+
+    fn children(r: &TranslatorRegistry<String, ()>, node: &ElementNode) -> String {
+        node.children.iter().map(|c| r.translate(c, ())).collect()
+    }
+
+    fn html() -> TranslatorRegistry<String, ()> {
+        let mut registry = TranslatorRegistry::new(
+            |r, node, ()| children(r, node),
+            |node, ()| node.content.to_string(),
+            |_, ()| String::new(),
+        );
+        registry
+            .on("p", |r, node, ()| format!("<p>{}</p>", children(r, node)))
+            .on("em", |r, node, ()| format!("<em>{}</em>", children(r, node)))
+            .on("link", |r, node, ()| {
+                format!("<a href=\"{}\">{}</a>", node.attributes["target"], children(r, node))
+            });
+        registry
+    }
+
+    fn translate(registry: &TranslatorRegistry<String, ()>, s: &str) -> String {
+        Parser::call(s)
+            .unwrap()
+            .iter()
+            .map(|n| registry.translate(n, ()))
+            .collect()
+    }
+
+    #[test]
+    fn dispatch_and_fallback() {
+        assert_eq!(
+            translate(&html(), "#p a %em{b} %kbd{c} %link[target=/x]{d}"),
+            "<p>a <em>b</em> c <a href=\"/x\">d</a></p>"
+        );
+    }
+
+    #[test]
+    fn override_and_predicates() {
+        let mut registry = html();
+        registry
+            .on("link", |r, node, ()| format!("[{}]", children(r, node)))
+            .on_if(
+                "link",
+                |node| node.attributes.get("target").is_some_and(|t| t.starts_with("http")),
+                |r, node, ()| format!("<a rel=\"external\">{}</a>", children(r, node)),
+            ).on_other(|_, node, ()| format!("?{}", node.name));
+
+        assert_eq!(
+            translate(&registry, "#p %link[target=/x]{a} %link[target=http://y]{b} %kbd{c}"),
+            "<p>[a] <a rel=\"external\">b</a> ?kbd</p>"
+        );
+    }
+
+    #[test]
+    fn extend() {
+        let mut overrides = TranslatorRegistry::new(
+            |_, _, ()| String::new(),
+            |_, ()| String::new(),
+            |_, ()| String::new(),
+        );
+        overrides.on("em", |r, node, ()| format!("*{}*", children(r, node)));
+
+        let mut registry = html();
+        registry.extend(overrides);
+        assert_eq!(translate(&registry, "#p a %em{b}"), "<p>a *b*</p>");
+    }
+}
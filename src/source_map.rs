@@ -0,0 +1,250 @@
+use super::parser::strip_bom;
+use super::{Node, ParseError, Parser, ParserOptions};
+
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The id of the next source map, so that file ids of different maps differ.
+static NEXT_MAP_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Identifies a source in a [`SourceMap`](struct.SourceMap.html). Ids from
+/// one map are not found in another, except in clones of that map.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FileId {
+    map: usize,
+    index: usize,
+}
+
+/// A position in a source, with lines and columns starting at 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub file: FileId,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// A parse error in one of the sources of a
+/// [`SourceMap`](struct.SourceMap.html).
+#[derive(Debug, PartialEq)]
+pub struct SourceError {
+    pub error: ParseError,
+    pub span: Span,
+
+    /// The name of the source, so that the error can be shown without the
+    /// source map.
+    pub file_name: String,
+}
+
+impl fmt::Display for SourceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: {:?}",
+            self.file_name, self.span.line, self.span.column, self.error
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Source {
+    name: String,
+    contents: String,
+}
+
+/// Holds several named sources, e.g. the files of a book or fragments that
+/// were generated, so that errors point to the right one.
+///
+/// # Examples
+///
+/// ```
+/// use d_mark::{ParserOptions, SourceMap};
+///
+/// let mut sources = SourceMap::new();
+/// sources.add("chapter1.dmark", "#p Fine");
+/// let chapter2 = sources.add("chapter2.dmark", "#p Fine\n#p %em");
+///
+/// let error = sources.parse(chapter2, ParserOptions::default()).unwrap().unwrap_err();
+/// assert_eq!(sources.name(error.span.file), Some("chapter2.dmark"));
+/// assert_eq!(error.to_string(), "chapter2.dmark:2:7: UnexpectedEOF");
+/// ```
+#[derive(Debug, Clone)]
+pub struct SourceMap {
+    id: usize,
+    sources: Vec<Source>,
+}
+
+impl Default for SourceMap {
+    fn default() -> Self {
+        SourceMap {
+            id: NEXT_MAP_ID.fetch_add(1, Ordering::Relaxed),
+            sources: vec![],
+        }
+    }
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        SourceMap::default()
+    }
+
+    pub fn add<N: Into<String>, C: Into<String>>(&mut self, name: N, contents: C) -> FileId {
+        self.sources.push(Source {
+            name: name.into(),
+            contents: contents.into(),
+        });
+        self.file_id(self.sources.len() - 1)
+    }
+
+    fn file_id(&self, index: usize) -> FileId {
+        FileId {
+            map: self.id,
+            index,
+        }
+    }
+
+    fn source(&self, file: FileId) -> Option<&Source> {
+        if file.map == self.id {
+            self.sources.get(file.index)
+        } else {
+            None
+        }
+    }
+
+    /// The name of the given source, or None if it is not in this map.
+    pub fn name(&self, file: FileId) -> Option<&str> {
+        self.source(file).map(|s| s.name.as_str())
+    }
+
+    /// The contents of the given source, or None if it is not in this map.
+    pub fn contents(&self, file: FileId) -> Option<&str> {
+        self.source(file).map(|s| s.contents.as_str())
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = FileId> + '_ {
+        (0..self.sources.len()).map(move |i| self.file_id(i))
+    }
+
+    /// Parse the given source, or return None if it is not in this map.
+    pub fn parse(
+        &self,
+        file: FileId,
+        options: ParserOptions,
+    ) -> Option<Result<Vec<Node>, SourceError>> {
+        let source = self.source(file)?;
+        Some(Parser::call_with_options(&source.contents, options).map_err(|e| {
+            let span = Span {
+                file,
+                line: e.line(),
+                column: e.column(),
+            };
+            SourceError {
+                error: e.into_error(),
+                span,
+                file_name: source.name.clone(),
+            }
+        }))
+    }
+
+    /// Show the given error with its location, and the line it is on and the
+    /// one before. The lines are left out if the source is not in this map.
+    pub fn render(&self, error: &SourceError) -> String {
+        let color_red = "\u{1B}[31m";
+        let color_reset = "\u{1B}[0m";
+
+        let contents = match self.contents(error.span.file) {
+            Some(contents) => contents,
+            None => return error.to_string(),
+        };
+
+        let mut res = format!("{}\n\n", error);
+        let lines: Vec<&str> = strip_bom(contents).lines().collect();
+        let line = error.span.line;
+        for i in line.saturating_sub(2)..line {
+            res.push_str(lines.get(i).unwrap_or(&""));
+            res.push('\n');
+        }
+        res.push_str(&format!(
+            "{}{:>width$}{}",
+            color_red,
+            "↑",
+            color_reset,
+            width = error.span.column
+        ));
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SourceMap, Span};
+    use {ParseError, ParserOptions};
+
+    #[test]
+    fn several_sources() {
+        let mut sources = SourceMap::new();
+        let intro = sources.add("intro.dmark", "#p Hello");
+        let toc = sources.add("<toc>", "#ul\n  #li %link{");
+
+        assert_eq!(sources.files().collect::<Vec<_>>(), vec![intro, toc]);
+        assert_eq!(sources.name(toc), Some("<toc>"));
+        assert!(sources.parse(intro, ParserOptions::default()).unwrap().is_ok());
+
+        let error = sources.parse(toc, ParserOptions::default()).unwrap().unwrap_err();
+        assert_eq!(error.error, ParseError::UnexpectedEOF);
+        assert_eq!(
+            error.span,
+            Span {
+                file: toc,
+                line: 2,
+                column: 13,
+            }
+        );
+        assert_eq!(
+            sources.render(&error),
+            "<toc>:2:13: UnexpectedEOF\n\n#ul\n  #li %link{\n\u{1B}[31m            ↑\u{1B}[0m"
+        );
+    }
+
+    #[test]
+    fn first_line() {
+        let mut sources = SourceMap::new();
+        let file = sources.add("a.dmark", "#p }");
+
+        let error = sources.parse(file, ParserOptions::default()).unwrap().unwrap_err();
+        assert_eq!(error.to_string(), "a.dmark:1:4: UnexpectedRightBrace");
+        assert_eq!(
+            sources.render(&error),
+            "a.dmark:1:4: UnexpectedRightBrace\n\n#p }\n\u{1B}[31m   ↑\u{1B}[0m"
+        );
+    }
+
+    #[test]
+    fn byte_order_mark() {
+        let mut sources = SourceMap::new();
+        let file = sources.add("a.dmark", "\u{FEFF}#p }");
+
+        let error = sources.parse(file, ParserOptions::default()).unwrap().unwrap_err();
+        assert_eq!(
+            sources.render(&error),
+            "a.dmark:1:4: UnexpectedRightBrace\n\n#p }\n\u{1B}[31m   ↑\u{1B}[0m"
+        );
+    }
+
+    #[test]
+    fn foreign_file() {
+        let mut other = SourceMap::new();
+        other.add("a.dmark", "#p a");
+        let file = other.add("b.dmark", "#p }");
+        let error = other.parse(file, ParserOptions::default()).unwrap().unwrap_err();
+
+        // The same index in another map is a different file
+        let mut sources = SourceMap::new();
+        sources.add("c.dmark", "#p c");
+        sources.add("d.dmark", "#p d");
+        assert_eq!(sources.name(file), None);
+        assert_eq!(sources.contents(file), None);
+        assert_eq!(sources.parse(file, ParserOptions::default()), None);
+        assert_eq!(other.clone().name(file), Some("b.dmark"));
+        assert_eq!(sources.render(&error), "b.dmark:1:4: UnexpectedRightBrace");
+    }
+}
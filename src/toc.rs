@@ -0,0 +1,439 @@
+use super::util::text_content;
+use super::{ElementNode, Node, StringNode};
+
+use std::collections::HashMap;
+
+/// A heading in a [`Toc`](struct.Toc.html), with the headings below it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TocEntry {
+    pub level: usize,
+    pub title: String,
+    pub id: String,
+    pub children: Vec<TocEntry>,
+}
+
+/// The table of contents of a document, as built by
+/// [`TocBuilder`](struct.TocBuilder.html).
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Toc {
+    pub entries: Vec<TocEntry>,
+
+    /// The ids that more than one element asked for, either in their id
+    /// attribute or by having the same title.
+    pub duplicate_ids: Vec<String>,
+}
+
+impl Toc {
+    /// The table of contents as an element with the given name, containing
+    /// nested `ul` lists of links to the headings.
+    pub fn to_node(&self, name: &str) -> Node {
+        Node::Element(ElementNode {
+            name: name.to_string().into(),
+            attributes: HashMap::new(),
+            children: list(&self.entries),
+            block: true,
+        })
+    }
+}
+
+fn element(
+    name: &'static str,
+    attributes: HashMap<String, String>,
+    children: Vec<Node>,
+    block: bool,
+) -> Node {
+    Node::Element(ElementNode {
+        name: name.into(),
+        attributes,
+        children,
+        block,
+    })
+}
+
+fn list(entries: &[TocEntry]) -> Vec<Node> {
+    if entries.is_empty() {
+        return vec![];
+    }
+
+    let items = entries
+        .iter()
+        .map(|e| {
+            let mut attributes = HashMap::new();
+            attributes.insert("target".to_string(), format!("#{}", e.id));
+            let title = Node::String(StringNode {
+                content: e.title.clone().into(),
+            });
+
+            let mut children = vec![element("link", attributes, vec![title], false)];
+            children.extend(list(&e.children));
+            element("li", HashMap::new(), children, true)
+        }).collect();
+    vec![element("ul", HashMap::new(), items, true)]
+}
+
+/// Collects the headings of a document into a [`Toc`](struct.Toc.html), and
+/// gives each of them an id to link to.
+///
+/// Headings are the elements in `headings`, where the first one is level 1,
+/// and `title` elements in `section` elements, where the level is the number
+/// of sections they are in. Headings that have an id keep it; others get one
+/// made from their title, made unique among the ids before it with a `-2`,
+/// `-3`, … suffix, so that ids later in the document never change it. Elements
+/// named `placeholder` are filled with the table of contents.
+///
+/// # Examples
+///
+/// ```
+/// use d_mark::{DmarkTranslator, Parser, TocBuilder};
+///
+/// let mut nodes = Parser::call("#toc\n#h1 Install\n#h2 From source\n#h1 Usage")
+///     .expect("parsing failed");
+/// let toc = TocBuilder::new().apply(&mut nodes);
+///
+/// assert_eq!(toc.entries[0].children[0].id, "from-source");
+/// assert_eq!(
+///     DmarkTranslator::new().translate_document(&nodes),
+///     concat!(
+///         "#toc\n",
+///         "  #ul\n",
+///         "    #li %link[target=#install]{Install}\n",
+///         "      #ul\n",
+///         "        #li %link[target=#from-source]{From source}\n",
+///         "    #li %link[target=#usage]{Usage}\n",
+///         "#h1[id=install] Install\n",
+///         "#h2[id=from-source] From source\n",
+///         "#h1[id=usage] Usage\n",
+///     )
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct TocBuilder {
+    /// The names of heading elements, from level 1 down. Defaults to `h1` to
+    /// `h6`.
+    pub headings: Vec<String>,
+
+    /// The name of section elements, whose title elements are headings.
+    pub section: String,
+
+    /// The name of the title elements of sections.
+    pub title: String,
+
+    /// The attribute that holds the id of a heading, and of other elements
+    /// that can be linked to.
+    pub id_attribute: String,
+
+    /// The name of elements to fill with the table of contents, or None to
+    /// leave the document as it is, apart from the ids.
+    pub placeholder: Option<String>,
+}
+
+impl Default for TocBuilder {
+    fn default() -> Self {
+        TocBuilder {
+            headings: (1..7).map(|i| format!("h{}", i)).collect(),
+            section: "section".into(),
+            title: "title".into(),
+            id_attribute: "id".into(),
+            placeholder: Some("toc".into()),
+        }
+    }
+}
+
+struct State {
+    /// How many elements before the current one asked for each id.
+    ids: HashMap<String, usize>,
+    entries: Vec<TocEntry>,
+    duplicate_ids: Vec<String>,
+}
+
+impl TocBuilder {
+    pub fn new() -> Self {
+        TocBuilder::default()
+    }
+
+    /// Give the headings in the given nodes an id, fill the placeholders, and
+    /// return the table of contents.
+    pub fn apply(&self, nodes: &mut [Node]) -> Toc {
+        let mut state = State {
+            ids: HashMap::new(),
+            entries: vec![],
+            duplicate_ids: vec![],
+        };
+        self.assign_ids(nodes, 0, &mut state);
+
+        let toc = Toc {
+            entries: nest(state.entries),
+            duplicate_ids: state.duplicate_ids,
+        };
+        if let Some(ref placeholder) = self.placeholder {
+            fill(nodes, placeholder, &toc);
+        }
+        toc
+    }
+
+    fn level(&self, node: &ElementNode, sections: usize) -> Option<usize> {
+        if node.name == self.title && sections > 0 {
+            Some(sections)
+        } else {
+            self.headings.iter().position(|h| *h == node.name).map(|i| i + 1)
+        }
+    }
+
+    fn assign_ids(&self, nodes: &mut [Node], sections: usize, state: &mut State) {
+        for node in nodes {
+            let n = match node {
+                Node::Element(n) => n,
+                _ => continue,
+            };
+
+            let explicit_id = n.attributes.get(&self.id_attribute).cloned();
+            if let Some(ref id) = explicit_id {
+                let count = state.ids.entry(id.clone()).or_insert(0);
+                *count += 1;
+                if *count == 2 {
+                    state.duplicate_ids.push(id.clone());
+                }
+            }
+
+            if let Some(level) = self.level(n, sections) {
+                let title = text_content(&n.children)
+                    .split_whitespace()
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                let id = match explicit_id {
+                    Some(id) => id,
+                    None => unique_id(slug(&title), state),
+                };
+                n.attributes.insert(self.id_attribute.clone(), id.clone());
+                state.entries.push(TocEntry {
+                    level,
+                    title,
+                    id,
+                    children: vec![],
+                });
+            }
+
+            let sections = sections + (n.name == self.section) as usize;
+            self.assign_ids(&mut n.children, sections, state);
+        }
+    }
+}
+
+/// Turn a title into an id: lowercase letters and digits, with dashes between
+/// words.
+fn slug(title: &str) -> String {
+    let mut res = String::new();
+    for c in title.chars() {
+        if c.is_alphanumeric() {
+            res.extend(c.to_lowercase());
+        } else if (c.is_whitespace() || c == '-' || c == '_')
+            && !res.is_empty()
+            && !res.ends_with('-')
+        {
+            res.push('-');
+        }
+    }
+    if res.ends_with('-') {
+        res.pop();
+    }
+    if res.is_empty() {
+        res.push_str("section");
+    }
+    res
+}
+
+fn unique_id(slug: String, state: &mut State) -> String {
+    if !state.ids.contains_key(&slug) {
+        state.ids.insert(slug.clone(), 1);
+        return slug;
+    }
+
+    if !state.duplicate_ids.contains(&slug) {
+        state.duplicate_ids.push(slug.clone());
+    }
+    let id = (2..)
+        .map(|i| format!("{}-{}", slug, i))
+        .find(|id| !state.ids.contains_key(id))
+        .expect("no free id");
+    state.ids.insert(id.clone(), 1);
+    id
+}
+
+/// Nest each entry under the closest entry before it with a lower level.
+fn nest(entries: Vec<TocEntry>) -> Vec<TocEntry> {
+    let mut stack: Vec<TocEntry> = vec![];
+    let mut res = vec![];
+
+    for entry in entries {
+        while stack.last().is_some_and(|e| e.level >= entry.level) {
+            close(&mut stack, &mut res);
+        }
+        stack.push(entry);
+    }
+    while !stack.is_empty() {
+        close(&mut stack, &mut res);
+    }
+    res
+}
+
+fn close(stack: &mut Vec<TocEntry>, res: &mut Vec<TocEntry>) {
+    let entry = stack.pop().expect("empty stack");
+    match stack.last_mut() {
+        Some(parent) => parent.children.push(entry),
+        None => res.push(entry),
+    }
+}
+
+fn fill(nodes: &mut [Node], placeholder: &str, toc: &Toc) {
+    for node in nodes {
+        if let Node::Element(n) = node {
+            if n.name == placeholder {
+                n.children = list(&toc.entries);
+            } else {
+                fill(&mut n.children, placeholder, toc);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{slug, TocBuilder, TocEntry};
+    use {DmarkTranslator, Parser};
+
+    fn entry(level: usize, title: &str, id: &str, children: Vec<TocEntry>) -> TocEntry {
+        TocEntry {
+            level,
+            title: title.into(),
+            id: id.into(),
+            children,
+        }
+    }
+
+    #[test]
+    fn slugs() {
+        assert_eq!(slug("Hello, World!"), "hello-world");
+        assert_eq!(slug("  The %em{new} -- API_v2 "), "the-emnew-api-v2");
+        assert_eq!(slug("Ünïcödé Straße"), "ünïcödé-straße");
+        assert_eq!(slug("?!"), "section");
+    }
+
+    #[test]
+    fn headings() {
+        let mut nodes = Parser::call(concat!(
+            "#h1 Intro\n",
+            "#h3 Deep\n",
+            "#h2 Usage %em{now}\n",
+            "#h2[id=intro] Again\n",
+            "#h1 Intro\n",
+            "#h1 Intro",
+        )).unwrap();
+        let toc = TocBuilder::new().apply(&mut nodes);
+
+        assert_eq!(
+            toc.entries,
+            vec![
+                entry(
+                    1,
+                    "Intro",
+                    "intro",
+                    vec![
+                        entry(3, "Deep", "deep", vec![]),
+                        entry(2, "Usage now", "usage-now", vec![]),
+                        entry(2, "Again", "intro", vec![]),
+                    ]
+                ),
+                entry(1, "Intro", "intro-2", vec![]),
+                entry(1, "Intro", "intro-3", vec![]),
+            ]
+        );
+        assert_eq!(toc.duplicate_ids, vec!["intro".to_string()]);
+    }
+
+    #[test]
+    fn later_ids_keep_earlier_ones() {
+        let mut nodes = Parser::call("#h1 Install
+#h1 Usage
+#p[id=usage-2] x
+#h1 Usage").unwrap();
+        let toc = TocBuilder::new().apply(&mut nodes);
+
+        assert_eq!(
+            toc.entries,
+            vec![
+                entry(1, "Install", "install", vec![]),
+                entry(1, "Usage", "usage", vec![]),
+                entry(1, "Usage", "usage-3", vec![]),
+            ]
+        );
+        assert_eq!(toc.duplicate_ids, vec!["usage".to_string()]);
+
+        let mut nodes = Parser::call("#h1 Install
+#h2[id=install] Again").unwrap();
+        let toc = TocBuilder::new().apply(&mut nodes);
+        assert_eq!(toc.entries[0].id, "install");
+        assert_eq!(toc.duplicate_ids, vec!["install".to_string()]);
+    }
+
+    #[test]
+    fn sections() {
+        let mut nodes = Parser::call(concat!(
+            "#section\n",
+            "  #title A\n",
+            "  #section[id=b]\n",
+            "    #title[id=x] B\n",
+            "    #p[id=x] text\n",
+            "#section\n",
+            "  #title C\n",
+            "#title Not a heading",
+        )).unwrap();
+        let builder = TocBuilder {
+            headings: vec![],
+            placeholder: None,
+            ..TocBuilder::new()
+        };
+        let toc = builder.apply(&mut nodes);
+
+        assert_eq!(
+            toc.entries,
+            vec![
+                entry(1, "A", "a", vec![entry(2, "B", "x", vec![])]),
+                entry(1, "C", "c", vec![]),
+            ]
+        );
+        assert_eq!(toc.duplicate_ids, vec!["x".to_string()]);
+
+        let mut translator = DmarkTranslator::new();
+        translator.block_elements.push("title".into());
+        assert_eq!(
+            translator.translate_document(&nodes),
+            concat!(
+                "#section\n",
+                "  #title[id=a] A\n",
+                "  #section[id=b]\n",
+                "    #title[id=x] B\n",
+                "    #p[id=x] text\n",
+                "#section\n",
+                "  #title[id=c] C\n",
+                "#title Not a heading\n",
+            )
+        );
+    }
+
+    #[test]
+    fn to_node() {
+        let mut nodes = Parser::call("#h1 A\n#h2 B").unwrap();
+        let toc = TocBuilder::new().apply(&mut nodes);
+
+        assert_eq!(
+            DmarkTranslator::new().translate_document(&[toc.to_node("nav")]),
+            concat!(
+                "#nav\n",
+                "  #ul\n",
+                "    #li %link[target=#a]{A}\n",
+                "      #ul\n",
+                "        #li %link[target=#b]{B}\n",
+            )
+        );
+    }
+}
@@ -1,22 +1,266 @@
-use super::{ElementNode, Node, StringNode};
+use super::{CommentNode, ElementNode, Node, StringNode};
+
+use std::fmt;
+use std::io;
 
 pub trait Translator<T, C> {
     fn translate(&self, node: &Node, context: C) -> T {
         match node {
             Node::Element(n) => self.translate_element(n, context),
             Node::String(n) => self.translate_string(n, context),
+            Node::Comment(n) => self.translate_comment(n, context),
         }
     }
 
     fn translate_element(&self, node: &ElementNode, context: C) -> T;
     fn translate_string(&self, node: &StringNode, context: C) -> T;
+
+    /// Translate a comment, which the parser only keeps when asked to. By
+    /// default, comments are translated like an empty string.
+    fn translate_comment(&self, _node: &CommentNode, context: C) -> T {
+        self.translate_string(&StringNode { content: "".into() }, context)
+    }
+}
+
+/// Nodes that a [`TranslationError`](struct.TranslationError.html) can be
+/// made for.
+pub trait ShallowClone {
+    /// A copy of the node, leaving out the children of an element.
+    fn shallow_clone(&self) -> Node;
+}
+
+impl ShallowClone for ElementNode {
+    fn shallow_clone(&self) -> Node {
+        Node::Element(ElementNode {
+            name: self.name.clone(),
+            attributes: self.attributes.clone(),
+            children: vec![],
+            block: self.block,
+        })
+    }
+}
+
+impl ShallowClone for StringNode {
+    fn shallow_clone(&self) -> Node {
+        Node::String(self.clone())
+    }
+}
+
+impl ShallowClone for CommentNode {
+    fn shallow_clone(&self) -> Node {
+        Node::Comment(self.clone())
+    }
+}
+
+impl ShallowClone for Node {
+    fn shallow_clone(&self) -> Node {
+        match self {
+            Node::Element(n) => n.shallow_clone(),
+            Node::String(n) => n.shallow_clone(),
+            Node::Comment(n) => n.shallow_clone(),
+        }
+    }
+}
+
+/// An error from a [`TryTranslator`](trait.TryTranslator.html), along with the
+/// node that caused it.
+#[derive(Debug, PartialEq)]
+pub struct TranslationError<E> {
+    pub error: E,
+
+    /// The node, without the children of an element, so that an error does
+    /// not copy a whole subtree. Boxed to keep the `Result`s holding the error
+    /// small.
+    pub node: Box<Node>,
+
+    /// The position of the node, as the indices of it and its ancestors in
+    /// their parents' children, starting with the outermost node that was
+    /// translated with `try_translate_children`.
+    ///
+    /// This is the only location an error has: nodes do not keep their line
+    /// and column in the source.
+    pub path: Vec<usize>,
+}
+
+impl<E> TranslationError<E> {
+    /// An error for the given element, string, comment or node.
+    pub fn new<N: ShallowClone>(node: &N, error: E) -> Self {
+        TranslationError {
+            error,
+            node: Box::new(node.shallow_clone()),
+            path: vec![],
+        }
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for TranslationError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let path: Vec<String> = self.path.iter().map(|i| i.to_string()).collect();
+        match *self.node {
+            Node::Element(ref n) => write!(
+                f,
+                "{} (at /{}, in #{})",
+                self.error,
+                path.join("/"),
+                n.name
+            ),
+            _ => write!(f, "{} (at /{})", self.error, path.join("/")),
+        }
+    }
+}
+
+/// Like [`Translator`](trait.Translator.html), but for translations that can
+/// fail, e.g. on unknown elements or invalid attributes.
+pub trait TryTranslator<T, C> {
+    type Error;
+
+    fn try_translate(&self, node: &Node, context: C) -> Result<T, TranslationError<Self::Error>> {
+        match node {
+            Node::Element(n) => self.try_translate_element(n, context),
+            Node::String(n) => self.try_translate_string(n, context),
+            Node::Comment(n) => self.try_translate_comment(n, context),
+        }
+    }
+
+    /// Translate the given nodes, stopping at the first error, and adding the
+    /// position of the failing node to it.
+    fn try_translate_children(
+        &self,
+        nodes: &[Node],
+        context: C,
+    ) -> Result<Vec<T>, TranslationError<Self::Error>>
+    where
+        C: Copy,
+    {
+        nodes
+            .iter()
+            .enumerate()
+            .map(|(i, n)| {
+                self.try_translate(n, context).map_err(|mut e| {
+                    e.path.insert(0, i);
+                    e
+                })
+            }).collect()
+    }
+
+    fn try_translate_element(
+        &self,
+        node: &ElementNode,
+        context: C,
+    ) -> Result<T, TranslationError<Self::Error>>;
+    fn try_translate_string(
+        &self,
+        node: &StringNode,
+        context: C,
+    ) -> Result<T, TranslationError<Self::Error>>;
+
+    /// Translate a comment. By default, comments are translated like an
+    /// empty string.
+    fn try_translate_comment(
+        &self,
+        _node: &CommentNode,
+        context: C,
+    ) -> Result<T, TranslationError<Self::Error>> {
+        self.try_translate_string(&StringNode { content: "".into() }, context)
+    }
+}
+
+/// A translator that writes its output as it goes, rather than building a
+/// value for every node, so that large documents are rendered in linear time.
+///
+/// Elements are written in three steps: `enter_element`, then the children
+/// (each with the context from `child_context`), then `leave_element`.
+pub trait WriteTranslator<C: Copy> {
+    fn write_node(&self, node: &Node, context: C, out: &mut dyn fmt::Write) -> fmt::Result {
+        match node {
+            Node::Element(n) => {
+                self.enter_element(n, context, out)?;
+                self.write_nodes(&n.children, self.child_context(n, context), out)?;
+                self.leave_element(n, context, out)
+            }
+            Node::String(n) => self.write_string(n, context, out),
+            Node::Comment(n) => self.write_comment(n, context, out),
+        }
+    }
+
+    fn write_nodes(&self, nodes: &[Node], context: C, out: &mut dyn fmt::Write) -> fmt::Result {
+        for node in nodes {
+            self.write_node(node, context, out)?;
+        }
+        Ok(())
+    }
+
+    /// Write the nodes to the given `io::Write`, e.g. a file.
+    fn write_nodes_to_io(
+        &self,
+        nodes: &[Node],
+        context: C,
+        out: &mut dyn io::Write,
+    ) -> io::Result<()> {
+        let mut adapter = IoAdapter { inner: out, error: None };
+        match self.write_nodes(nodes, context, &mut adapter) {
+            Ok(()) => Ok(()),
+            Err(fmt::Error) => Err(adapter
+                .error
+                .unwrap_or_else(|| io::Error::other("formatter error"))),
+        }
+    }
+
+    /// The context for the children of the given element. By default, the
+    /// children get the context of the element itself.
+    fn child_context(&self, _node: &ElementNode, context: C) -> C {
+        context
+    }
+
+    fn enter_element(
+        &self,
+        node: &ElementNode,
+        context: C,
+        out: &mut dyn fmt::Write,
+    ) -> fmt::Result;
+    fn leave_element(
+        &self,
+        node: &ElementNode,
+        context: C,
+        out: &mut dyn fmt::Write,
+    ) -> fmt::Result;
+    fn write_string(&self, node: &StringNode, context: C, out: &mut dyn fmt::Write) -> fmt::Result;
+
+    /// Write a comment. By default, comments are written like an empty
+    /// string.
+    fn write_comment(
+        &self,
+        _node: &CommentNode,
+        context: C,
+        out: &mut dyn fmt::Write,
+    ) -> fmt::Result {
+        self.write_string(&StringNode { content: "".into() }, context, out)
+    }
+}
+
+/// Lets a `WriteTranslator` write to an `io::Write`, keeping the I/O error,
+/// which `fmt::Error` cannot carry.
+struct IoAdapter<'a> {
+    inner: &'a mut dyn io::Write,
+    error: Option<io::Error>,
+}
+
+impl<'a> fmt::Write for IoAdapter<'a> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.inner.write_all(s.as_bytes()).map_err(|e| {
+            self.error = Some(e);
+            fmt::Error
+        })
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Translator;
-    use super::{ElementNode, Node, StringNode};
+    use super::{CommentNode, ElementNode, Node, StringNode};
+    use super::{TranslationError, Translator, TryTranslator, WriteTranslator};
     use std::collections::HashMap;
+    use std::fmt;
+    use std::io;
 
     struct SampleStringTranslator {}
 
@@ -37,6 +281,10 @@ mod tests {
         fn translate_string(&self, node: &StringNode, _context: ()) -> String {
             format!("str({:?})", node.content)
         }
+
+        fn translate_comment(&self, node: &CommentNode, _context: ()) -> String {
+            format!("comment({:?})", node.content)
+        }
     }
 
     #[derive(PartialEq, Debug)]
@@ -50,7 +298,7 @@ mod tests {
     impl Translator<TreeNode, ()> for SampleTreeTranslator {
         fn translate_element(&self, node: &ElementNode, context: ()) -> TreeNode {
             TreeNode::Elem(
-                node.name.to_owned().to_string(),
+                node.name.to_string(),
                 node.children
                     .iter()
                     .map(|c| self.translate(c, context))
@@ -59,7 +307,7 @@ mod tests {
         }
 
         fn translate_string(&self, node: &StringNode, _context: ()) -> TreeNode {
-            TreeNode::Str(node.content.to_owned().to_string())
+            TreeNode::Str(node.content.to_string())
         }
     }
 
@@ -89,7 +337,7 @@ mod tests {
         }
 
         fn translate_string(&self, node: &StringNode, _context: u8) -> String {
-            node.content.to_owned().to_string()
+            node.content.to_string()
         }
     }
 
@@ -104,6 +352,7 @@ mod tests {
             children: vec![Node::String(StringNode {
                 content: "child-str".into(),
             })],
+            block: true,
         });
         assert_eq!(
             SampleStringTranslator {}.translate(&input, ()),
@@ -123,6 +372,7 @@ mod tests {
             children: vec![Node::String(StringNode {
                 content: "child-str".into(),
             })],
+            block: true,
         });
         assert_eq!(
             SampleTreeTranslator {}.translate(&input, ()),
@@ -145,6 +395,7 @@ mod tests {
                     children: vec![Node::String(StringNode {
                         content: "foo".into(),
                     })],
+                    block: false,
                 }),
                 Node::Element(ElementNode {
                     name: "section".into(),
@@ -155,9 +406,12 @@ mod tests {
                         children: vec![Node::String(StringNode {
                             content: "bar".into(),
                         })],
+                        block: false,
                     })],
+                    block: false,
                 }),
             ],
+            block: true,
         });
 
         assert_eq!(
@@ -165,4 +419,181 @@ mod tests {
             "<h1>foo</h1><h2>bar</h2>".to_string()
         );
     }
+
+    #[test]
+    fn default_comment() {
+        let options = ::ParserOptions {
+            keep_comments: true,
+            ..::ParserOptions::default()
+        };
+        let parsed = ::Parser::call_with_options(
+            "#section\n  #header foo\n  #! dropped\n  #section\n    #header bar %!{dropped}",
+            options,
+        ).unwrap();
+        assert!(match parsed[0] {
+            Node::Element(ref n) => n.children.iter().any(|c| matches!(c, Node::Comment(_))),
+            _ => false,
+        });
+
+        let translated: Vec<String> = parsed
+            .iter()
+            .map(|n| SampleNestedTranslator {}.translate(n, 0))
+            .collect();
+        assert_eq!(translated, vec!["<h1>foo</h1><h2>bar </h2>".to_string()]);
+        assert_eq!(
+            SampleFallibleTranslator {}.try_translate_children(&parsed, 0),
+            Ok(vec!["<h1>foo</h1><h2>bar </h2>".to_string()])
+        );
+        let mut res = String::new();
+        SampleWriteTranslator {}.write_nodes(&parsed, 0, &mut res).unwrap();
+        assert_eq!(res, "<h1>foo</h1><h2>bar </h2>");
+    }
+
+    struct SampleFallibleTranslator {}
+
+    impl TryTranslator<String, u8> for SampleFallibleTranslator {
+        type Error = String;
+
+        fn try_translate_element(
+            &self,
+            node: &ElementNode,
+            context: u8,
+        ) -> Result<String, TranslationError<String>> {
+            match node.name.as_ref() {
+                "section" => Ok(self
+                    .try_translate_children(&node.children, context + 1)?
+                    .join("")),
+                "header" => Ok(format!(
+                    "<h{}>{}</h{}>",
+                    context,
+                    self.try_translate_children(&node.children, context + 1)?.join(""),
+                    context
+                )),
+                _ => Err(TranslationError::new(node, "unknown element".to_string())),
+            }
+        }
+
+        fn try_translate_string(
+            &self,
+            node: &StringNode,
+            _context: u8,
+        ) -> Result<String, TranslationError<String>> {
+            Ok(node.content.to_string())
+        }
+    }
+
+    #[test]
+    fn example_fallible() {
+        let parsed = ::Parser::call("#section\n  #header foo\n  #section\n    #header bar").unwrap();
+        assert_eq!(
+            SampleFallibleTranslator {}.try_translate_children(&parsed, 0),
+            Ok(vec!["<h1>foo</h1><h2>bar</h2>".to_string()])
+        );
+    }
+
+    #[test]
+    fn example_fallible_error() {
+        let parsed = ::Parser::call("#section\n  #header foo\n  #section\n    #header bar %em{baz}").unwrap();
+        let error = SampleFallibleTranslator {}
+            .try_translate_children(&parsed, 0)
+            .unwrap_err();
+        assert_eq!(error.path, vec![0, 1, 0, 1]);
+        assert_eq!(error.to_string(), "unknown element (at /0/1/0/1, in #em)");
+        match *error.node {
+            Node::Element(ref n) => {
+                assert_eq!(n.name(), "em");
+                assert_eq!(n.children, vec![]);
+            }
+            _ => panic!("expected an element"),
+        }
+    }
+
+    #[test]
+    fn error_for_any_node() {
+        let node = StringNode {
+            content: "foo".into(),
+        };
+        let error = TranslationError::new(&node, "unexpected text");
+        assert_eq!(*error.node, Node::String(node));
+        assert_eq!(error.to_string(), "unexpected text (at /)");
+    }
+
+    struct SampleWriteTranslator {}
+
+    impl WriteTranslator<u8> for SampleWriteTranslator {
+        fn child_context(&self, node: &ElementNode, context: u8) -> u8 {
+            if node.name == "section" {
+                context + 1
+            } else {
+                context
+            }
+        }
+
+        fn enter_element(
+            &self,
+            node: &ElementNode,
+            context: u8,
+            out: &mut dyn fmt::Write,
+        ) -> fmt::Result {
+            match node.name.as_ref() {
+                "header" => write!(out, "<h{}>", context),
+                _ => Ok(()),
+            }
+        }
+
+        fn leave_element(
+            &self,
+            node: &ElementNode,
+            context: u8,
+            out: &mut dyn fmt::Write,
+        ) -> fmt::Result {
+            match node.name.as_ref() {
+                "header" => write!(out, "</h{}>", context),
+                _ => Ok(()),
+            }
+        }
+
+        fn write_string(
+            &self,
+            node: &StringNode,
+            _context: u8,
+            out: &mut dyn fmt::Write,
+        ) -> fmt::Result {
+            out.write_str(&node.content)
+        }
+    }
+
+    #[test]
+    fn example_write() {
+        let parsed = ::Parser::call("#section\n  #header foo\n  #section\n    #header bar").unwrap();
+
+        let mut res = String::new();
+        SampleWriteTranslator {}.write_nodes(&parsed, 0, &mut res).unwrap();
+        assert_eq!(res, "<h1>foo</h1><h2>bar</h2>");
+
+        let mut res: Vec<u8> = vec![];
+        SampleWriteTranslator {}.write_nodes_to_io(&parsed, 0, &mut res).unwrap();
+        assert_eq!(res, b"<h1>foo</h1><h2>bar</h2>");
+    }
+
+    struct FailingWriter {}
+
+    impl io::Write for FailingWriter {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::BrokenPipe, "closed"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn example_write_io_error() {
+        let parsed = ::Parser::call("#section\n  #header foo").unwrap();
+        let error = SampleWriteTranslator {}
+            .write_nodes_to_io(&parsed, 0, &mut FailingWriter {})
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::BrokenPipe);
+    }
 }
@@ -0,0 +1,252 @@
+use super::{ElementNode, Node};
+
+use std::iter;
+use std::iter::FromIterator;
+
+#[derive(Debug, Clone, Copy)]
+struct Frame<'a> {
+    node: &'a ElementNode,
+    parent: Option<&'a Frame<'a>>,
+    depth: usize,
+}
+
+/// A translation context that knows where the node being translated is: its
+/// ancestors, its depth and its index among its siblings.
+///
+/// Use [`TraversalContext::default()`](#impl-Default) for the top-level nodes,
+/// and [`map_children`](#method.map_children) to translate the children of an
+/// element, so that they get a context of their own.
+///
+/// # Examples
+///
+/// ```
+/// use d_mark::{CommentNode, ElementNode, Parser, StringNode, TraversalContext, Translator};
+///
+/// struct HtmlTranslator;
+///
+/// impl<'a> Translator<String, TraversalContext<'a>> for HtmlTranslator {
+///     fn translate_element(&self, node: &ElementNode, context: TraversalContext<'a>) -> String {
+///         if context.attribute(node, "only").is_some_and(|o| o != "web") {
+///             return String::new();
+///         }
+///
+///         let content: String =
+///             context.map_children(node, |c, context| self.translate(c, context));
+///         match node.name() {
+///             "title" => {
+///                 let level = context.count_ancestors("section") + 1;
+///                 format!("<h{}>{}</h{}>", level, content, level)
+///             }
+///             _ => content,
+///         }
+///     }
+///
+///     fn translate_string(&self, node: &StringNode, _context: TraversalContext<'a>) -> String {
+///         node.content().to_string()
+///     }
+///
+///     fn translate_comment(&self, _node: &CommentNode, _context: TraversalContext<'a>) -> String {
+///         String::new()
+///     }
+/// }
+///
+/// let parsed = Parser::call("#section\n  #title A\n  #section[only=print]\n    #title B")
+///     .expect("parsing failed");
+/// let html: String = parsed
+///     .iter()
+///     .map(|n| HtmlTranslator.translate(n, TraversalContext::default()))
+///     .collect();
+/// assert_eq!(html, "<h2>A</h2>");
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TraversalContext<'a> {
+    parent: Option<&'a Frame<'a>>,
+    index: usize,
+}
+
+impl<'a> TraversalContext<'a> {
+    /// The number of ancestors of the node, i.e. zero for top-level nodes.
+    pub fn depth(&self) -> usize {
+        self.parent.map_or(0, |f| f.depth + 1)
+    }
+
+    /// The index of the node in the children of its parent.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn parent(&self) -> Option<&'a ElementNode> {
+        self.parent.map(|f| f.node)
+    }
+
+    /// The ancestors of the node, starting with its parent.
+    pub fn ancestors(&self) -> impl Iterator<Item = &'a ElementNode> {
+        iter::successors(self.parent, |f| f.parent).map(|f| f.node)
+    }
+
+    /// The number of ancestors with the given name, e.g. to find the level of
+    /// a heading in nested sections.
+    pub fn count_ancestors(&self, name: &str) -> usize {
+        self.ancestors().filter(|a| a.name == name).count()
+    }
+
+    /// The value of the given attribute on the nearest ancestor that has it.
+    pub fn inherited_attribute(&self, name: &str) -> Option<&'a str> {
+        self.ancestors()
+            .filter_map(|a| a.attributes.get(name))
+            .map(|v| v.as_str())
+            .next()
+    }
+
+    /// The value of the given attribute on the node, or else on the nearest
+    /// ancestor that has it.
+    pub fn attribute<'b>(&self, node: &'b ElementNode, name: &str) -> Option<&'b str>
+    where
+        'a: 'b,
+    {
+        node.attributes
+            .get(name)
+            .map(|v| v.as_str())
+            .or_else(|| self.inherited_attribute(name))
+    }
+
+    /// Translate the children of the given element, which is the node of this
+    /// context, by calling `f` with each child and its context. The results
+    /// are collected, e.g. into a `String`, a `Vec` or a `Result`, which stops
+    /// at the first error.
+    pub fn map_children<'n, T, R, F>(&self, node: &'n ElementNode, mut f: F) -> R
+    where
+        R: FromIterator<T>,
+        F: FnMut(&'n Node, TraversalContext) -> T,
+    {
+        let frame = Frame {
+            node,
+            parent: self.parent,
+            depth: self.depth(),
+        };
+        node.children
+            .iter()
+            .enumerate()
+            .map(|(index, c)| {
+                f(
+                    c,
+                    TraversalContext {
+                        parent: Some(&frame),
+                        index,
+                    },
+                )
+            }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TraversalContext;
+    use {CommentNode, ElementNode, Node, Parser, StringNode, TranslationError, Translator};
+    use TryTranslator;
+
+    struct SampleOutlineTranslator;
+
+    impl<'a> Translator<String, TraversalContext<'a>> for SampleOutlineTranslator {
+        fn translate_element(&self, node: &ElementNode, context: TraversalContext<'a>) -> String {
+            let children: String =
+                context.map_children(node, |c, context| self.translate(c, context));
+            format!(
+                "{}{}#{} ({}/{}, only={:?})\n{}",
+                "  ".repeat(context.depth()),
+                context.index(),
+                node.name,
+                context.parent().map_or("-", |p| p.name()),
+                context.count_ancestors("section"),
+                context.attribute(node, "only"),
+                children
+            )
+        }
+
+        fn translate_string(&self, _node: &StringNode, _context: TraversalContext<'a>) -> String {
+            String::new()
+        }
+
+        fn translate_comment(&self, _node: &CommentNode, _context: TraversalContext<'a>) -> String {
+            String::new()
+        }
+    }
+
+    #[test]
+    fn outline() {
+        let parsed = Parser::call(concat!(
+            "#section[only=web]\n",
+            "  #p a\n",
+            "  #section\n",
+            "    #p[only=print] b %em{c}\n",
+            "#p d",
+        )).unwrap();
+        let outline: String = parsed
+            .iter()
+            .map(|n| SampleOutlineTranslator.translate(n, TraversalContext::default()))
+            .collect();
+
+        assert_eq!(
+            outline,
+            concat!(
+                "0#section (-/0, only=Some(\"web\"))\n",
+                "  0#p (section/1, only=Some(\"web\"))\n",
+                "  1#section (section/1, only=Some(\"web\"))\n",
+                "    0#p (section/2, only=Some(\"print\"))\n",
+                "      1#em (p/2, only=Some(\"print\"))\n",
+                "0#p (-/0, only=None)\n",
+            )
+        );
+    }
+
+    struct SampleDepthLimitTranslator;
+
+    impl<'a> TryTranslator<usize, TraversalContext<'a>> for SampleDepthLimitTranslator {
+        type Error = String;
+
+        fn try_translate_element(
+            &self,
+            node: &ElementNode,
+            context: TraversalContext<'a>,
+        ) -> Result<usize, TranslationError<String>> {
+            if context.depth() > 2 {
+                return Err(TranslationError::new(node, "too deep".into()));
+            }
+            let counts: Result<Vec<usize>, _> =
+                context.map_children(node, |c, context| self.try_translate(c, context));
+            Ok(counts?.iter().sum::<usize>() + 1)
+        }
+
+        fn try_translate_string(
+            &self,
+            _node: &StringNode,
+            _context: TraversalContext<'a>,
+        ) -> Result<usize, TranslationError<String>> {
+            Ok(0)
+        }
+
+        fn try_translate_comment(
+            &self,
+            _node: &CommentNode,
+            _context: TraversalContext<'a>,
+        ) -> Result<usize, TranslationError<String>> {
+            Ok(0)
+        }
+    }
+
+    #[test]
+    fn fallible() {
+        let translate = |s: &str| {
+            let parsed = Parser::call(s).unwrap();
+            SampleDepthLimitTranslator
+                .try_translate(&parsed[0], TraversalContext::default())
+                .map_err(|e| match *e.node {
+                    Node::Element(n) => n.name.to_string(),
+                    _ => String::new(),
+                })
+        };
+
+        assert_eq!(translate("#ul\n  #li %em{a}\n  #li b"), Ok(4));
+        assert_eq!(translate("#ul\n  #li %em{%code{a}}"), Err("code".to_string()));
+    }
+}
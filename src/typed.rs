@@ -0,0 +1,201 @@
+use super::util::text_content;
+use super::{CommentNode, ElementNode, Node, StringNode, TranslationError, TryTranslator};
+
+use std::fmt;
+use std::marker::PhantomData;
+use std::str::FromStr;
+
+/// Why a node could not be turned into a typed value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypedError {
+    UnknownElement(String),
+    UnexpectedText,
+    UnexpectedComment,
+    MissingAttribute(String),
+    InvalidAttribute(String, String),
+}
+
+impl fmt::Display for TypedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TypedError::UnknownElement(name) => write!(f, "unknown element: {}", name),
+            TypedError::UnexpectedText => write!(f, "unexpected text"),
+            TypedError::UnexpectedComment => write!(f, "unexpected comment"),
+            TypedError::MissingAttribute(name) => write!(f, "missing attribute: {}", name),
+            TypedError::InvalidAttribute(name, message) => {
+                write!(f, "invalid attribute {}: {}", name, message)
+            }
+        }
+    }
+}
+
+/// Translates nodes into values of type `T`.
+///
+/// It implements [`TryTranslator`](trait.TryTranslator.html) for `String`
+/// (the text content of any node), and for types that derive `FromNode` from
+/// the `d-mark-derive` crate.
+pub struct TypedTranslator<T> {
+    marker: PhantomData<T>,
+}
+
+impl<T> Default for TypedTranslator<T> {
+    fn default() -> Self {
+        TypedTranslator {
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T> TypedTranslator<T> {
+    pub fn new() -> Self {
+        TypedTranslator::default()
+    }
+}
+
+impl<T> TypedTranslator<T>
+where
+    TypedTranslator<T>: TryTranslator<T, (), Error = TypedError>,
+{
+    /// Translate the given nodes, e.g. a whole document. Comments are left
+    /// out, and so is text containing only whitespace, unless `T` can be
+    /// built from it.
+    pub fn translate_nodes(&self, nodes: &[Node]) -> Result<Vec<T>, TranslationError<TypedError>> {
+        let mut res = vec![];
+        for (i, node) in nodes.iter().enumerate() {
+            let value = match node {
+                Node::Comment(_) => continue,
+                Node::String(n) => match self.try_translate_string(n, ()) {
+                    Err(ref e) if e.error == TypedError::UnexpectedText
+                        && n.content.trim().is_empty() =>
+                    {
+                        continue
+                    }
+                    value => value,
+                },
+                Node::Element(n) => self.try_translate_element(n, ()),
+            };
+            res.push(value.map_err(|mut e| {
+                e.path.insert(0, i);
+                e
+            })?);
+        }
+        Ok(res)
+    }
+}
+
+impl TryTranslator<String, ()> for TypedTranslator<String> {
+    type Error = TypedError;
+
+    fn try_translate_element(
+        &self,
+        node: &ElementNode,
+        _context: (),
+    ) -> Result<String, TranslationError<TypedError>> {
+        Ok(text_content(&node.children))
+    }
+
+    fn try_translate_string(
+        &self,
+        node: &StringNode,
+        _context: (),
+    ) -> Result<String, TranslationError<TypedError>> {
+        Ok(node.content.to_string())
+    }
+
+    fn try_translate_comment(
+        &self,
+        node: &CommentNode,
+        _context: (),
+    ) -> Result<String, TranslationError<TypedError>> {
+        Err(TranslationError::new(node, TypedError::UnexpectedComment))
+    }
+}
+
+/// Types that can be built from the children of an element.
+pub trait FromChildren: Sized {
+    fn from_children(node: &ElementNode) -> Result<Self, TranslationError<TypedError>>;
+}
+
+impl<T> FromChildren for Vec<T>
+where
+    TypedTranslator<T>: TryTranslator<T, (), Error = TypedError>,
+{
+    fn from_children(node: &ElementNode) -> Result<Self, TranslationError<TypedError>> {
+        TypedTranslator::new().translate_nodes(&node.children)
+    }
+}
+
+impl FromChildren for String {
+    fn from_children(node: &ElementNode) -> Result<Self, TranslationError<TypedError>> {
+        Ok(text_content(&node.children))
+    }
+}
+
+/// Parse the given attribute of the element, if it is there.
+pub fn parse_attribute<T>(
+    node: &ElementNode,
+    name: &str,
+) -> Result<Option<T>, TranslationError<TypedError>>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    match node.attributes.get(name) {
+        None => Ok(None),
+        Some(value) => value.parse().map(Some).map_err(|e: T::Err| {
+            TranslationError::new(
+                node,
+                TypedError::InvalidAttribute(name.to_string(), e.to_string()),
+            )
+        }),
+    }
+}
+
+/// Parse the given attribute of the element, which must be there.
+pub fn require_attribute<T>(
+    node: &ElementNode,
+    name: &str,
+) -> Result<T, TranslationError<TypedError>>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    parse_attribute(node, name)?.ok_or_else(|| {
+        TranslationError::new(node, TypedError::MissingAttribute(name.to_string()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_attribute, require_attribute, TypedError, TypedTranslator};
+    use {Node, Parser};
+
+    #[test]
+    fn strings() {
+        let parsed = Parser::call("#p a %em{b}\n#! c\n#p d").unwrap();
+        assert_eq!(
+            TypedTranslator::<String>::new().translate_nodes(&parsed),
+            Ok(vec!["a b".to_string(), "d".to_string()])
+        );
+    }
+
+    #[test]
+    fn attributes() {
+        let parsed = Parser::call("#release[version=12,date=x]").unwrap();
+        let node = match parsed[0] {
+            Node::Element(ref n) => n,
+            _ => panic!("expected an element"),
+        };
+
+        assert_eq!(require_attribute::<u32>(node, "version"), Ok(12));
+        assert_eq!(parse_attribute::<u32>(node, "other"), Ok(None));
+        assert_eq!(
+            require_attribute::<u32>(node, "other").unwrap_err().error,
+            TypedError::MissingAttribute("other".into())
+        );
+        assert_eq!(
+            require_attribute::<u32>(node, "date").unwrap_err().error,
+            TypedError::InvalidAttribute("date".into(), "invalid digit found in string".into())
+        );
+    }
+}
@@ -1,16 +1,28 @@
-pub trait FilterableResult<T, E> {
-    fn filter<P: FnOnce(&T) -> bool>(self, predicate: P, error: E) -> Result<T, E>;
+use super::Node;
+
+/// Whether the node is rendered as a block: either it was written as a block,
+/// or it contains one.
+pub fn is_block(node: &Node) -> bool {
+    match node {
+        Node::Element(n) => n.block || n.children.iter().any(is_block),
+        _ => false,
+    }
+}
+
+/// The concatenated content of all string nodes in the given nodes, at any
+/// depth.
+pub fn text_content(nodes: &[Node]) -> String {
+    let mut res = String::new();
+    push_text_content(nodes, &mut res);
+    res
 }
 
-impl<T, E> FilterableResult<T, E> for Result<T, E> {
-    fn filter<P: FnOnce(&T) -> bool>(self, predicate: P, error: E) -> Result<T, E> {
-        match self {
-            Ok(x) => if predicate(&x) {
-                return Ok(x);
-            } else {
-                return Err(error);
-            },
-            Err(x) => Err(x),
+fn push_text_content(nodes: &[Node], res: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Element(n) => push_text_content(&n.children, res),
+            Node::String(n) => res.push_str(&n.content),
+            Node::Comment(_) => (),
         }
     }
 }
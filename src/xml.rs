@@ -0,0 +1,429 @@
+use super::util::is_block;
+use super::parser::is_raw;
+use super::{CommentNode, ElementNode, Node, StringNode, Translator, RAW_ATTRIBUTE};
+
+use std::collections::HashMap;
+
+/// The DocBook 5 namespace.
+pub const DOCBOOK_NAMESPACE: &str = "http://docbook.org/ns/docbook";
+
+/// How the XML translator renders an element.
+#[derive(Debug, Clone, PartialEq)]
+pub struct XmlElement {
+    /// The name of the XML element.
+    pub name: String,
+
+    /// Attributes added to the XML element, e.g. `role="strong"`, unless the
+    /// D★Mark element has an attribute with the same name.
+    pub attributes: Vec<(String, String)>,
+
+    /// D★Mark attributes that get another name in XML, e.g. `target` becoming
+    /// `xlink:href`, replacing any attribute that already has that name.
+    /// Other attributes are kept as they are.
+    pub renamed_attributes: Vec<(String, String)>,
+
+    /// An element wrapped around runs of inline content, for XML elements that
+    /// only allow blocks, e.g. `para` in a DocBook `listitem`.
+    pub inline_wrapper: Option<String>,
+}
+
+impl XmlElement {
+    pub fn new(name: &str) -> Self {
+        XmlElement {
+            name: name.into(),
+            attributes: vec![],
+            renamed_attributes: vec![],
+            inline_wrapper: None,
+        }
+    }
+
+    pub fn with_attribute(mut self, name: &str, value: &str) -> Self {
+        self.attributes.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn with_renamed_attribute(mut self, from: &str, to: &str) -> Self {
+        self.renamed_attributes.push((from.into(), to.into()));
+        self
+    }
+
+    pub fn with_inline_wrapper(mut self, name: &str) -> Self {
+        self.inline_wrapper = Some(name.into());
+        self
+    }
+}
+
+/// Translates D★Mark to XML.
+///
+/// By default, every element, attribute and string is kept as it is, so that
+/// the tree can be read back without loss. The only exception are comments
+/// containing `--`, which XML does not allow, and characters that cannot
+/// appear in XML at all, which are replaced with U+FFFD.
+///
+/// Elements listed in `elements` are renamed, and lose their `raw` attribute,
+/// which is how [`XmlTranslator::docbook`](#method.docbook) produces DocBook.
+///
+/// # Examples
+///
+/// ```
+/// use d_mark::{Parser, XmlTranslator};
+///
+/// let parsed = Parser::call("#p[only=web] I %em{love} <Rust>").expect("parsing failed");
+/// let xml = XmlTranslator::new().translate_document(&parsed);
+/// assert_eq!(
+///     xml,
+///     "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+///      <d-mark><p only=\"web\">I <em>love</em> &lt;Rust&gt;</p></d-mark>\n"
+/// );
+/// ```
+pub struct XmlTranslator {
+    pub root: String,
+    pub namespace: Option<String>,
+    pub root_attributes: Vec<(String, String)>,
+    pub elements: HashMap<String, XmlElement>,
+}
+
+impl Default for XmlTranslator {
+    fn default() -> Self {
+        XmlTranslator {
+            root: "d-mark".into(),
+            namespace: None,
+            root_attributes: vec![],
+            elements: HashMap::new(),
+        }
+    }
+}
+
+impl XmlTranslator {
+    pub fn new() -> Self {
+        XmlTranslator::default()
+    }
+
+    /// A translator that maps the common elements to DocBook 5, in an
+    /// `article`. Sections nest, with `header` becoming the section `title`.
+    pub fn docbook() -> Self {
+        let mut elements = HashMap::new();
+        elements.insert("p".into(), XmlElement::new("para"));
+        elements.insert("section".into(), XmlElement::new("section"));
+        elements.insert("header".into(), XmlElement::new("title"));
+        for level in 1..6 {
+            elements.insert(
+                format!("h{}", level),
+                XmlElement::new("bridgehead").with_attribute("renderas", &format!("sect{}", level)),
+            );
+        }
+        elements.insert(
+            "h6".into(),
+            XmlElement::new("bridgehead").with_attribute("renderas", "other"),
+        );
+        elements.insert("em".into(), XmlElement::new("emphasis"));
+        elements.insert(
+            "strong".into(),
+            XmlElement::new("emphasis").with_attribute("role", "strong"),
+        );
+        elements.insert("code".into(), XmlElement::new("literal"));
+        elements.insert(
+            "link".into(),
+            XmlElement::new("link").with_renamed_attribute("target", "xlink:href"),
+        );
+        elements.insert(
+            "listing".into(),
+            XmlElement::new("programlisting").with_renamed_attribute("lang", "language"),
+        );
+        elements.insert("ul".into(), XmlElement::new("itemizedlist"));
+        elements.insert("ol".into(), XmlElement::new("orderedlist"));
+        elements.insert(
+            "li".into(),
+            XmlElement::new("listitem").with_inline_wrapper("para"),
+        );
+        elements.insert(
+            "blockquote".into(),
+            XmlElement::new("blockquote").with_inline_wrapper("para"),
+        );
+
+        XmlTranslator {
+            root: "article".into(),
+            namespace: Some(DOCBOOK_NAMESPACE.into()),
+            root_attributes: vec![
+                ("version".into(), "5.0".into()),
+                ("xmlns:xlink".into(), "http://www.w3.org/1999/xlink".into()),
+            ],
+            elements,
+        }
+    }
+
+    /// Translate a whole document, as returned by the parser, including the
+    /// XML declaration and the root element.
+    pub fn translate_document(&self, nodes: &[Node]) -> String {
+        let mut attributes = vec![];
+        if let Some(ref namespace) = self.namespace {
+            attributes.push(("xmlns".to_string(), namespace.clone()));
+        }
+        attributes.extend(self.root_attributes.iter().cloned());
+
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n{}\n",
+            tag(&self.root, &attributes, &self.translate_children(nodes))
+        )
+    }
+
+    fn translate_children(&self, nodes: &[Node]) -> String {
+        nodes.iter().map(|n| self.translate(n, ())).collect()
+    }
+
+    /// Translate nodes, wrapping runs of inline nodes that contain more than
+    /// whitespace in the given element.
+    fn translate_wrapped(&self, nodes: &[Node], wrapper: &str) -> String {
+        let mut res = String::new();
+        let mut inline: Vec<&Node> = vec![];
+
+        for node in nodes {
+            if is_block(node) {
+                res.push_str(&self.wrap_inline(&inline, wrapper));
+                inline.clear();
+                res.push_str(&self.translate(node, ()));
+            } else {
+                inline.push(node);
+            }
+        }
+        res.push_str(&self.wrap_inline(&inline, wrapper));
+
+        res
+    }
+
+    fn wrap_inline(&self, nodes: &[&Node], wrapper: &str) -> String {
+        let content: String = nodes.iter().map(|n| self.translate(n, ())).collect();
+        let only_whitespace = nodes.iter().all(|n| match n {
+            Node::String(s) => s.content.trim().is_empty(),
+            Node::Comment(_) => true,
+            Node::Element(_) => false,
+        });
+
+        if only_whitespace {
+            content
+        } else {
+            tag(wrapper, &[], &content)
+        }
+    }
+}
+
+impl Translator<String, ()> for XmlTranslator {
+    fn translate_element(&self, node: &ElementNode, _context: ()) -> String {
+        let mut attributes: Vec<(String, String)> = node
+            .attributes
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        attributes.sort();
+
+        match self.elements.get(node.name.as_ref()) {
+            Some(mapping) => {
+                // The raw attribute only tells the parser how to read the
+                // content, so it has no place in the target vocabulary.
+                if is_raw(&node.attributes) {
+                    attributes.retain(|(k, _)| k != RAW_ATTRIBUTE);
+                }
+                // A renamed attribute wins over one that already has its new name
+                attributes.retain(|(k, _)| {
+                    !mapping
+                        .renamed_attributes
+                        .iter()
+                        .any(|(from, to)| to == k && node.attributes.contains_key(from))
+                });
+                for attribute in &mut attributes {
+                    if let Some((_, to)) = mapping
+                        .renamed_attributes
+                        .iter()
+                        .find(|(from, _)| *from == attribute.0)
+                    {
+                        attribute.0 = to.clone();
+                    }
+                }
+                // The attributes of the node win over the fixed ones
+                let mut all_attributes: Vec<(String, String)> = mapping
+                    .attributes
+                    .iter()
+                    .filter(|(k, _)| attributes.iter().all(|(name, _)| name != k))
+                    .cloned()
+                    .collect();
+                all_attributes.extend(attributes);
+
+                let content = match mapping.inline_wrapper {
+                    Some(ref wrapper) => self.translate_wrapped(&node.children, wrapper),
+                    None => self.translate_children(&node.children),
+                };
+                tag(&mapping.name, &all_attributes, &content)
+            }
+            None => tag(&node.name, &attributes, &self.translate_children(&node.children)),
+        }
+    }
+
+    fn translate_string(&self, node: &StringNode, _context: ()) -> String {
+        escape(&node.content)
+    }
+
+    fn translate_comment(&self, node: &CommentNode, _context: ()) -> String {
+        let mut content = replace_invalid(&node.content);
+        while content.contains("--") {
+            content = content.replace("--", "- -");
+        }
+        if content.ends_with('-') {
+            content.push(' ');
+        }
+        format!("<!--{}-->", content)
+    }
+}
+
+fn tag(name: &str, attributes: &[(String, String)], content: &str) -> String {
+    let attributes: String = attributes
+        .iter()
+        .map(|(k, v)| format!(" {}=\"{}\"", k, escape_attribute(v)))
+        .collect();
+    if content.is_empty() {
+        format!("<{}{}/>", name, attributes)
+    } else {
+        format!("<{}{}>{}</{}>", name, attributes, content, name)
+    }
+}
+
+/// Whether the character can appear in an XML 1.0 document.
+fn is_xml_char(c: char) -> bool {
+    match c {
+        '\t' | '\n' | '\r' => true,
+        '\u{0}'..='\u{1F}' | '\u{FFFE}' | '\u{FFFF}' => false,
+        _ => true,
+    }
+}
+
+fn replace_invalid(s: &str) -> String {
+    s.chars()
+        .map(|c| if is_xml_char(c) { c } else { '\u{FFFD}' })
+        .collect()
+}
+
+/// Escape characters that are special in XML text.
+fn escape(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => res.push_str("&amp;"),
+            '<' => res.push_str("&lt;"),
+            '>' => res.push_str("&gt;"),
+            '\r' => res.push_str("&#13;"),
+            _ if !is_xml_char(c) => res.push('\u{FFFD}'),
+            _ => res.push(c),
+        }
+    }
+    res
+}
+
+/// Escape characters that are special in XML attribute values, including
+/// whitespace that would otherwise be normalized to spaces.
+fn escape_attribute(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => res.push_str("&amp;"),
+            '<' => res.push_str("&lt;"),
+            '"' => res.push_str("&quot;"),
+            '\t' => res.push_str("&#9;"),
+            '\n' => res.push_str("&#10;"),
+            '\r' => res.push_str("&#13;"),
+            _ if !is_xml_char(c) => res.push('\u{FFFD}'),
+            _ => res.push(c),
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::XmlTranslator;
+    use Parser;
+
+    fn xml(s: &str) -> String {
+        XmlTranslator::new().translate_document(&Parser::call(s).unwrap())
+    }
+
+    #[test]
+    fn elements_and_attributes() {
+        assert_eq!(
+            xml("#p[b=2,a=1] x %em{y}%br{}\n#p"),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<d-mark><p a=\"1\" b=\"2\">x <em>y</em><br/></p><p/></d-mark>\n"
+        );
+    }
+
+    #[test]
+    fn escaping() {
+        assert_eq!(
+            xml("#p[t=\"<&>\"] a < b && c > d\u{1} %%"),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<d-mark><p t=\"&quot;&lt;&amp;>&quot;\">a &lt; b &amp;&amp; c &gt; d\u{FFFD} %</p></d-mark>\n"
+        );
+    }
+
+    #[test]
+    fn comments() {
+        let parsed = Parser::call_with_options(
+            "#! a -- b-\n#p x",
+            ::ParserOptions {
+                keep_comments: true,
+                ..::ParserOptions::default()
+            },
+        ).unwrap();
+        assert_eq!(
+            XmlTranslator::new().translate_document(&parsed),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<d-mark><!-- a - - b- --><p>x</p></d-mark>\n"
+        );
+    }
+
+    #[test]
+    fn namespace() {
+        let translator = XmlTranslator {
+            namespace: Some("urn:x".into()),
+            ..XmlTranslator::new()
+        };
+        let parsed = Parser::call("#p x").unwrap();
+        assert_eq!(
+            translator.translate_document(&parsed),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<d-mark xmlns=\"urn:x\"><p>x</p></d-mark>\n"
+        );
+    }
+
+    #[test]
+    fn docbook() {
+        let parsed = Parser::call(
+            "#section\n  #header Intro\n  #p See %link[target=http://a.org]{%strong{this}}.\n  #ul\n    #li one\n    #li\n      #p two\n  #listing[raw,lang=sh]\n    ls",
+        ).unwrap();
+        assert_eq!(
+            XmlTranslator::docbook().translate_document(&parsed),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <article xmlns=\"http://docbook.org/ns/docbook\" version=\"5.0\" xmlns:xlink=\"http://www.w3.org/1999/xlink\">\
+             <section><title>Intro</title>\
+             <para>See <link xlink:href=\"http://a.org\"><emphasis role=\"strong\">this</emphasis></link>.</para>\
+             <itemizedlist><listitem><para>one</para></listitem><listitem><para>two</para></listitem></itemizedlist>\
+             <programlisting language=\"sh\">ls</programlisting></section></article>\n"
+        );
+    }
+
+    #[test]
+    fn docbook_renamed_attribute_wins() {
+        let parsed = Parser::call("#listing[raw,lang=sh,language=x] ls").unwrap();
+        assert_eq!(
+            XmlTranslator::docbook().translate_document(&parsed),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <article xmlns=\"http://docbook.org/ns/docbook\" version=\"5.0\" xmlns:xlink=\"http://www.w3.org/1999/xlink\">\
+             <programlisting language=\"sh\">ls</programlisting></article>\n"
+        );
+    }
+
+    #[test]
+    fn docbook_node_attribute_wins() {
+        let parsed = Parser::call("#p %strong[role=x]{a}").unwrap();
+        assert_eq!(
+            XmlTranslator::docbook().translate_document(&parsed),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <article xmlns=\"http://docbook.org/ns/docbook\" version=\"5.0\" xmlns:xlink=\"http://www.w3.org/1999/xlink\">\
+             <para><emphasis role=\"x\">a</emphasis></para></article>\n"
+        );
+    }
+}
@@ -0,0 +1,478 @@
+use super::dmark::DEFAULT_BLOCK_ELEMENTS;
+use super::{CommentNode, ElementNode, Node, ParserOptions, StringNode};
+
+use roxmltree;
+use std::collections::HashMap;
+use std::fmt;
+
+/// HTML entities that are common in XHTML pages, but not predefined in XML.
+const HTML_ENTITIES: &[(&str, &str)] = &[
+    ("nbsp", "&#160;"),
+    ("copy", "&#169;"),
+    ("reg", "&#174;"),
+    ("trade", "&#8482;"),
+    ("ndash", "&#8211;"),
+    ("mdash", "&#8212;"),
+    ("hellip", "&#8230;"),
+    ("lsquo", "&#8216;"),
+    ("rsquo", "&#8217;"),
+    ("ldquo", "&#8220;"),
+    ("rdquo", "&#8221;"),
+    ("laquo", "&#171;"),
+    ("raquo", "&#187;"),
+    ("middot", "&#183;"),
+    ("bull", "&#8226;"),
+    ("euro", "&#8364;"),
+];
+
+/// An error in the XML given to the importer.
+#[derive(Debug)]
+pub struct XmlImportError {
+    message: String,
+}
+
+impl fmt::Display for XmlImportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid XML: {}", self.message)
+    }
+}
+
+impl XmlImportError {
+    fn new<S: Into<String>>(message: S) -> Self {
+        XmlImportError {
+            message: message.into(),
+        }
+    }
+}
+
+/// Reads XML into a node tree, e.g. to be written out as D★Mark with
+/// [`DmarkTranslator`](struct.DmarkTranslator.html).
+///
+/// A `d-mark` root element, as written by
+/// [`XmlTranslator`](struct.XmlTranslator.html), is left out, and so is an
+/// XHTML `html` root element with its `head`: the content of `body` is
+/// imported. Any other root element is kept. Namespace prefixes are dropped
+/// from element and attribute names, except where two attributes of an element
+/// would then have the same name: those keep their prefix, e.g. `xml-lang`.
+/// Should the names still be the same, importing fails. Other ASCII characters
+/// that cannot appear
+/// in D★Mark names are replaced with dashes, and non-ASCII ones with their code
+/// point, e.g. `_f6_` for `ö`. With `unicode_names`, Unicode letters and digits
+/// are kept instead, and the result must be parsed with
+/// [`ParserOptions::unicode_names`](struct.ParserOptions.html#structfield.unicode_names).
+///
+/// With `collapse_whitespace`, runs of whitespace become a single space, and
+/// whitespace at the start and end of blocks and between blocks is dropped, as
+/// a browser would, except in `preformatted_elements`.
+///
+/// With `html_entities`, common HTML entities such as `&nbsp;` can be used in
+/// text and attribute values, as if the document type declared them.
+///
+/// # Examples
+///
+/// ```
+/// use d_mark::{DmarkTranslator, XmlImporter};
+///
+/// let nodes = XmlImporter::xhtml()
+///     .import("<html><body>\n  <p>I <em>love</em>\n    Rust</p>\n</body></html>")
+///     .expect("importing failed");
+/// let dmark = DmarkTranslator::new().translate_document(&nodes);
+/// assert_eq!(dmark, "#p I %em{love} Rust\n");
+/// ```
+pub struct XmlImporter {
+    pub collapse_whitespace: bool,
+    pub html_entities: bool,
+    pub unicode_names: bool,
+    pub block_elements: Vec<String>,
+    pub preformatted_elements: Vec<String>,
+}
+
+impl Default for XmlImporter {
+    fn default() -> Self {
+        XmlImporter {
+            collapse_whitespace: false,
+            html_entities: false,
+            unicode_names: false,
+            block_elements: DEFAULT_BLOCK_ELEMENTS.iter().map(|e| e.to_string()).collect(),
+            preformatted_elements: vec!["pre".into(), "listing".into()],
+        }
+    }
+}
+
+impl XmlImporter {
+    /// An importer that keeps all whitespace, e.g. to read back what the XML
+    /// translator wrote.
+    pub fn new() -> Self {
+        XmlImporter::default()
+    }
+
+    /// An importer for XHTML pages, which collapses whitespace and knows the
+    /// common HTML entities.
+    pub fn xhtml() -> Self {
+        XmlImporter {
+            collapse_whitespace: true,
+            html_entities: true,
+            ..XmlImporter::default()
+        }
+    }
+
+    pub fn import(&self, s: &str) -> Result<Vec<Node>, XmlImportError> {
+        let with_entities;
+        let s = if self.html_entities {
+            with_entities = declare_html_entities(s);
+            &with_entities
+        } else {
+            s
+        };
+
+        let options = roxmltree::ParsingOptions {
+            allow_dtd: true,
+            ..roxmltree::ParsingOptions::default()
+        };
+        let document = roxmltree::Document::parse_with_options(s, options)
+            .map_err(|e| XmlImportError::new(e.to_string()))?;
+
+        let root = document.root_element();
+        let content = match root.tag_name().name() {
+            "d-mark" => root,
+            "html" => root
+                .children()
+                .find(|n| n.tag_name().name() == "body")
+                .unwrap_or(root),
+            _ => return Ok(self.import_node(root, false)?.into_iter().collect()),
+        };
+
+        let mut nodes = self.import_children(content, false)?;
+        self.trim_block_content(&mut nodes);
+        Ok(nodes)
+    }
+
+    fn import_children(
+        &self,
+        node: roxmltree::Node,
+        preformatted: bool,
+    ) -> Result<Vec<Node>, XmlImportError> {
+        let mut res = vec![];
+        for child in node.children() {
+            res.extend(self.import_node(child, preformatted)?);
+        }
+        Ok(res)
+    }
+
+    fn import_node(
+        &self,
+        node: roxmltree::Node,
+        preformatted: bool,
+    ) -> Result<Option<Node>, XmlImportError> {
+        if node.is_element() {
+            let name = node.tag_name().name();
+            let preformatted = preformatted || self.preformatted_elements.iter().any(|e| e == name);
+
+            let attributes = self.import_attributes(node)?;
+            let mut children = self.import_children(node, preformatted)?;
+            if self.is_block_name(name) && !preformatted {
+                self.trim_block_content(&mut children);
+            }
+
+            Ok(Some(Node::Element(ElementNode {
+                name: self.to_name(name).into(),
+                attributes,
+                children,
+                block: self.is_block_name(name),
+            })))
+        } else if node.is_text() {
+            let text = node.text().unwrap_or("");
+            let content = if self.collapse_whitespace && !preformatted {
+                collapse(text)
+            } else {
+                text.to_string()
+            };
+            Ok(Some(Node::String(StringNode {
+                content: content.into(),
+            })))
+        } else if node.is_comment() {
+            Ok(Some(Node::Comment(CommentNode {
+                content: node.text().unwrap_or("").to_string().into(),
+            })))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// The attributes of the element, without namespace prefixes unless two
+    /// of them would then have the same name.
+    fn import_attributes(
+        &self,
+        node: roxmltree::Node,
+    ) -> Result<HashMap<String, String>, XmlImportError> {
+        let local_names: Vec<String> = node.attributes().map(|a| self.to_name(a.name())).collect();
+
+        let mut res = HashMap::new();
+        for (attribute, local_name) in node.attributes().zip(&local_names) {
+            let prefix = attribute.namespace().and_then(|ns| node.lookup_prefix(ns));
+            let name = match prefix {
+                Some(prefix) if local_names.iter().filter(|n| *n == local_name).count() > 1 => {
+                    self.to_name(&format!("{}:{}", prefix, attribute.name()))
+                }
+                _ => local_name.clone(),
+            };
+            if res.insert(name, attribute.value().to_string()).is_some() {
+                return Err(XmlImportError::new(format!(
+                    "attributes of {} have the same name once imported",
+                    node.tag_name().name()
+                )));
+            }
+        }
+        Ok(res)
+    }
+
+    /// Turn an XML name into a valid D★Mark name.
+    fn to_name(&self, name: &str) -> String {
+        let options = ParserOptions {
+            unicode_names: self.unicode_names,
+            ..ParserOptions::default()
+        };
+        let mut res = String::with_capacity(name.len());
+        for c in name.chars() {
+            if options.is_name_tail_char(c) {
+                res.push(c);
+            } else if c.is_ascii() {
+                res.push('-');
+            } else {
+                res.push_str(&format!("_{:x}_", c as u32));
+            }
+        }
+        if !res.starts_with(|c| options.is_name_head_char(c)) {
+            res.insert(0, 'x');
+        }
+        res
+    }
+
+    fn is_block_name(&self, name: &str) -> bool {
+        self.block_elements.iter().any(|e| e == name)
+    }
+
+    fn is_block(&self, node: Option<&Node>) -> bool {
+        match node {
+            Some(Node::Element(n)) => self.is_block_name(&n.name),
+            _ => false,
+        }
+    }
+
+    /// Drop whitespace at the start and end of the given block content, and
+    /// around blocks in it.
+    fn trim_block_content(&self, nodes: &mut Vec<Node>) {
+        if !self.collapse_whitespace {
+            return;
+        }
+
+        let len = nodes.len();
+        for i in 0..len {
+            let at_start = i == 0 || self.is_block(nodes.get(i - 1));
+            let at_end = i + 1 == len || self.is_block(nodes.get(i + 1));
+            if let Node::String(ref mut s) = nodes[i] {
+                let mut content: &str = &s.content;
+                if at_start {
+                    content = content.trim_start();
+                }
+                if at_end {
+                    content = content.trim_end();
+                }
+                s.content = content.to_string().into();
+            }
+        }
+
+        nodes.retain(|n| match n {
+            Node::String(s) => !s.content.is_empty(),
+            _ => true,
+        });
+    }
+}
+
+/// Declare the HTML entities in the document type, so that the XML parser
+/// expands them in text and attribute values, but not in comments or CDATA
+/// sections.
+fn declare_html_entities(s: &str) -> String {
+    let declarations: String = HTML_ENTITIES
+        .iter()
+        .map(|(name, reference)| format!("<!ENTITY {} \"{}\">", name, reference))
+        .collect();
+
+    let start = prolog_end(s);
+    let rest = &s[start..];
+    if !rest.starts_with("<!DOCTYPE") {
+        return format!("{}<!DOCTYPE d-mark [{}]>{}", &s[..start], declarations, rest);
+    }
+    match rest.find(['[', '>']).map(|i| start + i) {
+        Some(i) if s[i..].starts_with('[') => {
+            format!("{}{}{}", &s[..i + 1], declarations, &s[i + 1..])
+        }
+        Some(i) => format!("{} [{}]{}", &s[..i], declarations, &s[i..]),
+        // Leave it to the parser to report the broken document type
+        None => s.to_string(),
+    }
+}
+
+/// The index after the byte order mark, XML declaration, processing
+/// instructions, comments and whitespace that may come before the document
+/// type.
+fn prolog_end(s: &str) -> usize {
+    let mut i = if s.starts_with('\u{feff}') { 3 } else { 0 };
+    loop {
+        let rest = &s[i..];
+        let trimmed = rest.trim_start();
+        i += rest.len() - trimmed.len();
+        let end = if trimmed.starts_with("<?") {
+            trimmed.find("?>").map(|e| e + 2)
+        } else if trimmed.starts_with("<!--") {
+            trimmed.find("-->").map(|e| e + 3)
+        } else {
+            None
+        };
+        match end {
+            Some(end) => i += end,
+            None => return i,
+        }
+    }
+}
+
+fn collapse(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    let mut in_whitespace = false;
+    for c in s.chars() {
+        if c.is_whitespace() && c != '\u{A0}' {
+            if !in_whitespace {
+                res.push(' ');
+            }
+            in_whitespace = true;
+        } else {
+            res.push(c);
+            in_whitespace = false;
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::XmlImporter;
+    use {DmarkTranslator, Parser, ParserOptions, XmlTranslator};
+
+    fn xhtml(s: &str) -> String {
+        DmarkTranslator::new().translate_document(&XmlImporter::xhtml().import(s).unwrap())
+    }
+
+    #[test]
+    fn xhtml_page() {
+        assert_eq!(
+            xhtml(
+                "<?xml version=\"1.0\"?>\n<!DOCTYPE html>\n\
+                 <html xmlns=\"http://www.w3.org/1999/xhtml\"><head><title>x</title></head><body>\n\
+                 <h1 xml:lang=\"en\">A&nbsp;title</h1>\n\
+                 <!-- note -->\n\
+                 <div class=\"intro\">\n  <p>Some <a href=\"/x\">link</a>,\n  100% <b>bold</b>.</p>\n</div>\n\
+                 <pre>  #x\n  y</pre>\n\
+                 </body></html>"
+            ),
+            "#h1[lang=en] A\u{A0}title\n#! note \n#div[class=intro]\n  #p Some %a[href=/x]{link}, 100%% %b{bold}.\n#pre   %#x\n    y\n"
+        );
+    }
+
+    #[test]
+    fn other_root() {
+        assert_eq!(
+            xhtml("<db:article xmlns:db=\"urn:db\"><db:para>x</db:para></db:article>"),
+            "#article %para{x}\n"
+        );
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(xhtml("<x.y a.b=\"1\"><_z/></x.y>"), "#x-y[a-b=1] %x_z{}\n");
+    }
+
+    #[test]
+    fn non_ascii_names() {
+        let xml = "<zitat größe=\"1\">x</zitat>";
+        let dmark = xhtml(xml);
+        assert_eq!(dmark, "#zitat[gr_f6__df_e=1] x\n");
+        assert!(Parser::call(&dmark).is_ok());
+
+        let importer = XmlImporter {
+            unicode_names: true,
+            ..XmlImporter::xhtml()
+        };
+        let dmark = DmarkTranslator::new().translate_document(&importer.import(xml).unwrap());
+        assert_eq!(dmark, "#zitat[größe=1] x\n");
+        let options = ParserOptions {
+            unicode_names: true,
+            ..ParserOptions::default()
+        };
+        let parsed = Parser::call_with_options(&dmark, options).unwrap();
+        assert_eq!(DmarkTranslator::new().translate_document(&parsed), dmark);
+    }
+
+    #[test]
+    fn html_entities() {
+        let importer = XmlImporter {
+            html_entities: true,
+            ..XmlImporter::new()
+        };
+        let import = |s| {
+            importer
+                .import(s)
+                .map(|nodes| DmarkTranslator::new().translate_document(&nodes))
+                .unwrap()
+        };
+
+        assert_eq!(
+            import("<p title=\"a&ndash;b\">&copy; <![CDATA[&copy;]]><!--&copy;--></p>"),
+            "#p[title=a\u{2013}b] \u{A9} &copy;%!{&copy;}\n"
+        );
+        assert_eq!(
+            import(
+                "<?xml version=\"1.0\"?>\n<!--x-->\n\
+                 <!DOCTYPE p [<!ENTITY me \"Me\">]><p>&me;&hellip;</p>"
+            ),
+            "#p Me\u{2026}\n"
+        );
+        assert!(XmlImporter::new().import("<p>&copy;</p>").is_err());
+    }
+
+    #[test]
+    fn prefixed_attributes() {
+        assert_eq!(
+            xhtml("<p xml:lang=\"en\" lang=\"de\" xmlns:x=\"urn:x\" x:id=\"1\">a</p>"),
+            "#p[id=1,lang=de,xml-lang=en] a\n"
+        );
+
+        let error = XmlImporter::new()
+            .import("<p xmlns:a=\"urn:a\" a:b=\"1\" b=\"2\" a-b=\"3\">a</p>")
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid XML: attributes of p have the same name once imported"
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        let error = XmlImporter::new().import("<p>a</q>").unwrap_err();
+        assert!(error.to_string().starts_with("invalid XML: "));
+    }
+
+    #[test]
+    fn xml_round_trip() {
+        let options = ParserOptions {
+            keep_comments: true,
+            ..ParserOptions::default()
+        };
+        let parsed = Parser::call_with_options(
+            "#! c\n#section[id=a]\n  #p one %em[x=1]{two} <&>\n    three\n  #listing[raw]\n     x\n",
+            options,
+        ).unwrap();
+        let xml = XmlTranslator::new().translate_document(&parsed);
+        let imported = XmlImporter::new().import(&xml).unwrap();
+        assert_eq!(XmlTranslator::new().translate_document(&imported), xml);
+    }
+}
@@ -0,0 +1,62 @@
+//! Property tests asserting that the parser never panics, whatever the input.
+
+extern crate d_mark;
+extern crate proptest;
+
+use d_mark::{Parser, ParserOptions};
+use proptest::prelude::*;
+
+/// Input built from the characters that are significant to D★Mark, so that
+/// generated documents get deep into the parser rather than failing on the
+/// first character.
+const DMARK_ISH: &str = "[#%!{}\\[\\]=, \t\r\n\u{FEFF}a-cé1_-]{0,64}";
+
+/// A single line, with some indentation and possibly a (raw) block start.
+const DMARK_ISH_LINE: &str = "( {0,5}|\t{0,2})(#[a-c]+(\\[raw\\])? ?)?[#%!{}\\[\\]=, a-cé1_-]{0,32}";
+
+fn all_options() -> Vec<ParserOptions> {
+    let mut res = vec![];
+    for &unicode_names in &[false, true] {
+        for &tab_indentation in &[false, true] {
+            for &keep_comments in &[false, true] {
+                for &raw_blocks in &[false, true] {
+                    res.push(ParserOptions {
+                        unicode_names,
+                        tab_indentation,
+                        keep_comments,
+                        raw_blocks,
+                        ..ParserOptions::default()
+                    });
+                }
+            }
+        }
+    }
+    res
+}
+
+fn check(s: &str) {
+    for options in all_options() {
+        if let Err(error) = Parser::call_with_options(s, options) {
+            let _ = format!("{}", error);
+        }
+    }
+}
+
+proptest! {
+    #[test]
+    fn never_panics_on_arbitrary_input(s in "\\PC{0,64}") {
+        check(&s);
+    }
+
+    #[test]
+    fn never_panics_on_dmark_like_input(s in DMARK_ISH) {
+        check(&s);
+    }
+
+    #[test]
+    fn never_panics_on_block_input(
+        lines in prop::collection::vec(DMARK_ISH_LINE, 0..8)
+    ) {
+        check(&lines.join("\n"));
+    }
+}